@@ -0,0 +1,113 @@
+//! candump-style frame formatting and filtering.
+
+use embedded_can::{ExtendedId, Frame as CanFrame, Id, StandardId};
+use std::fmt::Write;
+use tritiumcan::{datagram::Frame, filter::Rule};
+
+/// Format a frame as a candump line for the given interface name.
+pub fn format(interface: &str, frame: &Frame) -> String {
+    let mut line = match CanFrame::id(frame) {
+        Id::Standard(id) => format!("{interface}  {:03X}", id.as_raw()),
+        Id::Extended(id) => format!("{interface}  {:08X}", id.as_raw()),
+    };
+
+    write!(line, "   [{}] ", CanFrame::dlc(frame)).unwrap();
+
+    if frame.is_remote_frame() {
+        line.push_str(" remote request");
+    } else {
+        for byte in CanFrame::data(frame) {
+            write!(line, " {byte:02X}").unwrap();
+        }
+    }
+
+    line
+}
+
+/// Maximum number of `<id>:<mask>` filters.
+pub const MAX_FILTERS: usize = 16;
+
+/// Acceptance filters for printed frames.
+pub type Filters = tritiumcan::filter::Filters<MAX_FILTERS>;
+
+/// Parse an acceptance rule in candump `<id>:<mask>` notation.
+///
+/// Identifiers written with more than three hex digits match extended frames,
+/// as they do in candump.
+pub fn parse_filter(s: &str) -> Result<Rule, String> {
+    let (id, mask) = s
+        .split_once(':')
+        .ok_or_else(|| format!("filter {s:?} must be <id>:<mask>"))?;
+    let parse = |v: &str| {
+        u32::from_str_radix(v, 16).map_err(|_| format!("invalid hex {v:?}"))
+    };
+    let (raw, mask) = (parse(id)?, parse(mask)?);
+
+    if id.len() > 3 {
+        let id = ExtendedId::new(raw)
+            .ok_or_else(|| format!("invalid extended id {id:?}"))?;
+        Ok(Rule::extended_mask(id, mask))
+    } else {
+        let id = u16::try_from(raw)
+            .ok()
+            .and_then(StandardId::new)
+            .ok_or_else(|| format!("invalid standard id {id:?}"))?;
+        Ok(Rule::standard_mask(id, mask as u16))
+    }
+}
+
+/// Collect parsed rules, at most [`MAX_FILTERS`].
+pub fn filters(rules: &[Rule]) -> Filters {
+    let mut filters = Filters::new();
+    for rule in rules {
+        filters.add(*rule);
+    }
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Frame, tritiumcan::datagram::ParseFrameError> {
+        s.parse()
+    }
+
+    #[test]
+    fn parse_and_format() {
+        let frame = parse("123#DE.AD.BE.EF").unwrap();
+        assert_eq!(
+            format("tritium13", &frame),
+            "tritium13  123   [4]  DE AD BE EF"
+        );
+
+        let frame = parse("1ABCDEF0#").unwrap();
+        assert!(frame.is_extended());
+        assert_eq!(format("tritium0", &frame), "tritium0  1ABCDEF0   [0] ");
+
+        let frame = parse("7FF#R2").unwrap();
+        assert!(frame.is_remote_frame());
+        assert_eq!(CanFrame::dlc(&frame), 2);
+
+        assert!(parse("800#00").is_err());
+        assert!(parse("12#00").is_err());
+        assert!(parse("123#000").is_err());
+        assert!(parse("123#000000000000000000").is_err());
+    }
+
+    #[test]
+    fn filter() {
+        let accepted = filters(&[parse_filter("100:7F0").unwrap()]);
+        assert!(accepted.matches(&parse("10A#").unwrap()));
+        assert!(!accepted.matches(&parse("20A#").unwrap()));
+        assert!(!accepted.matches(&parse("0000010A#").unwrap()));
+
+        let accepted = filters(&[parse_filter("0000010A:1FFFFFFF").unwrap()]);
+        assert!(accepted.matches(&parse("0000010A#").unwrap()));
+        assert!(!accepted.matches(&parse("10A#").unwrap()));
+
+        assert!(parse_filter("800:7FF").is_err());
+        assert!(parse_filter("20000000:0").is_err());
+        assert!(parse_filter("100").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Command-line tool for Tritium CAN-Ethernet adapters.
+
+mod frame;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand};
+use frame::Filters;
+use tritiumcan::{
+    candump,
+    datagram::{Frame, Heartbeat, Message},
+    filter::Rule,
+    BusNumber, PORT,
+};
+use tritiumcan_std::{tcp, udp};
+
+#[derive(Parser)]
+#[command(name = "tritium", version, about)]
+struct Cli {
+    /// Local interface address used to join the multicast group.
+    #[arg(long, global = true, default_value_t = Ipv4Addr::UNSPECIFIED)]
+    interface: Ipv4Addr,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List adapters found from their heartbeats.
+    Discover {
+        /// How long to listen for heartbeats, in seconds.
+        #[arg(short, long, default_value_t = 3)]
+        time: u64,
+    },
+    /// Print live traffic.
+    Dump {
+        /// Bus number to listen on.
+        #[arg(short, long, default_value_t = 13, value_parser = parse_bus_number)]
+        bus: u8,
+        /// Print the time since start with each frame.
+        #[arg(short, long)]
+        timestamp: bool,
+        /// Print frames in `candump -L` log format.
+        #[arg(short = 'L', long, conflicts_with = "timestamp")]
+        log: bool,
+        /// Only print frames matching any of these `<id>:<mask>` filters.
+        #[arg(num_args = 0..=frame::MAX_FILTERS, value_parser = frame::parse_filter)]
+        filters: Vec<Rule>,
+    },
+    /// Send a frame in `ID#DATA` notation.
+    Send {
+        /// Bus number to send on.
+        #[arg(short, long, default_value_t = 13, value_parser = parse_bus_number)]
+        bus: u8,
+        /// Frame to send, e.g. `123#DEADBEEF` or `1ABCDEF0#R`.
+        frame: Frame,
+    },
+    /// Send the frames of a `candump -L` log, keeping their timing.
+    Replay {
+        /// Bus number to send on.
+        #[arg(short, long, default_value_t = 13, value_parser = parse_bus_number)]
+        bus: u8,
+        /// Log file, or `-` for standard input.
+        file: PathBuf,
+    },
+    /// TCP sessions.
+    #[command(subcommand)]
+    Tcp(TcpCommand),
+}
+
+#[derive(Subcommand)]
+enum TcpCommand {
+    /// Connect to an adapter and print its traffic.
+    Connect {
+        /// Adapter address, the protocol port is used if none is given.
+        addr: String,
+        /// Bus number announced to the adapter.
+        #[arg(short, long, default_value_t = 13, value_parser = parse_bus_number)]
+        bus: u8,
+        /// Print the time since start with each frame.
+        #[arg(short, long)]
+        timestamp: bool,
+        /// Print frames in `candump -L` log format.
+        #[arg(short = 'L', long, conflicts_with = "timestamp")]
+        log: bool,
+    },
+}
+
+fn parse_bus_number(s: &str) -> Result<u8, String> {
+    let value: u8 = s.parse().map_err(|_| format!("invalid number {s:?}"))?;
+    BusNumber::try_from(value)
+        .map(u8::from)
+        .map_err(|_| format!("bus number {value} is larger than 15"))
+}
+
+fn bus_number(value: u8) -> BusNumber {
+    // validated by `parse_bus_number`
+    BusNumber::try_from(value).unwrap()
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let config = udp::Config {
+        multicast: udp::Config::default()
+            .multicast
+            .map(|(group, _)| (group, cli.interface)),
+        ..Default::default()
+    };
+
+    let result = match cli.command {
+        Command::Discover { time } => {
+            discover(&config, Duration::from_secs(time))
+        }
+        Command::Dump {
+            bus,
+            timestamp,
+            log,
+            filters,
+        } => {
+            let output = Output::new(timestamp, log);
+            let filters = frame::filters(&filters);
+            dump(&config, bus_number(bus), output, &filters)
+        }
+        Command::Send { bus, frame } => send(&config, bus_number(bus), &frame),
+        Command::Replay { bus, file } => {
+            replay(&config, bus_number(bus), &file)
+        }
+        Command::Tcp(TcpCommand::Connect {
+            addr,
+            bus,
+            timestamp,
+            log,
+        }) => {
+            let output = Output::new(timestamp, log);
+            tcp_connect(&addr, bus_number(bus), output)
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn discover(config: &udp::Config, time: Duration) -> io::Result<()> {
+    let mut client = udp::Client::bind(config, BusNumber::default())?;
+    let start = Instant::now();
+    let mut adapters: BTreeMap<[u8; 6], (Heartbeat, SocketAddr)> =
+        BTreeMap::new();
+
+    while let Some(remaining) = time.checked_sub(start.elapsed()) {
+        if remaining.is_zero() {
+            break;
+        }
+        client.set_read_timeout(Some(remaining))?;
+
+        match client.recv_from() {
+            Ok((Message::Heartbeat(heartbeat), addr)) => {
+                adapters.insert(heartbeat.mac_addr, (heartbeat, addr));
+            }
+            Ok(_) => {}
+            Err(err) if is_timeout(&err) => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    println!("{:<4} {:>8}  {:<17}  ADDRESS", "BUS", "BITRATE", "MAC");
+    for (heartbeat, addr) in adapters.values() {
+        let mac = heartbeat.mac_addr.map(|b| format!("{b:02X}")).join(":");
+        println!(
+            "{:<4} {:>8}  {mac}  {}",
+            u8::from(heartbeat.bus_number),
+            format!("{}k", u16::from(heartbeat.data_rate)),
+            addr.ip(),
+        );
+    }
+
+    Ok(())
+}
+
+fn dump(
+    config: &udp::Config,
+    bus_number: BusNumber,
+    output: Output,
+    filters: &Filters,
+) -> io::Result<()> {
+    let mut client = udp::Client::bind(config, bus_number)?;
+    let interface = format!("tritium{}", u8::from(bus_number));
+
+    loop {
+        let frame = client.recv_frame()?;
+        print_frame(&interface, &frame, output, filters);
+    }
+}
+
+/// Client for sending without receiving.
+fn sender(
+    config: &udp::Config,
+    bus_number: BusNumber,
+) -> io::Result<udp::Client> {
+    let bind = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
+    let config = udp::Config {
+        bind,
+        multicast: None,
+        ..config.clone()
+    };
+
+    udp::Client::bind(&config, bus_number)
+}
+
+fn send(
+    config: &udp::Config,
+    bus_number: BusNumber,
+    frame: &Frame,
+) -> io::Result<()> {
+    sender(config, bus_number)?.send_frame(frame)
+}
+
+fn replay(
+    config: &udp::Config,
+    bus_number: BusNumber,
+    file: &Path,
+) -> io::Result<()> {
+    let log: Box<dyn BufRead> = match file.to_str() {
+        Some("-") => Box::new(io::stdin().lock()),
+        _ => Box::new(BufReader::new(File::open(file)?)),
+    };
+
+    let client = sender(config, bus_number)?;
+    let mut first = None;
+    let start = Instant::now();
+
+    for entry in candump::Reader::new(log) {
+        let entry = entry?;
+
+        // frames are sent at the same offsets from the first as logged
+        let first = *first.get_or_insert(entry.timestamp);
+        let offset = entry.timestamp.saturating_sub(first);
+        if let Some(wait) = offset.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+
+        client.send_frame(&entry.frame)?;
+    }
+
+    Ok(())
+}
+
+fn tcp_connect(
+    addr: &str,
+    bus_number: BusNumber,
+    output: Output,
+) -> io::Result<()> {
+    let mut connection = match addr.parse::<SocketAddr>() {
+        Ok(addr) => tcp::Connection::connect(addr, bus_number)?,
+        Err(_) => tcp::Connection::connect((addr, PORT), bus_number)?,
+    };
+
+    let remote = connection.remote_bus_number();
+    eprintln!(
+        "connected to {} on bus {}",
+        connection.peer_addr()?,
+        u8::from(remote)
+    );
+
+    let interface = format!("tritium{}", u8::from(remote));
+
+    loop {
+        let frame = connection.recv_frame()?;
+        print_frame(&interface, &frame, output, &Filters::new());
+    }
+}
+
+/// How received frames are printed.
+#[derive(Clone, Copy)]
+enum Output {
+    /// candump default format, with the time since `start` if set.
+    Plain { start: Option<Instant> },
+    /// `candump -L` log format.
+    Log,
+}
+
+impl Output {
+    fn new(timestamp: bool, log: bool) -> Self {
+        match log {
+            true => Output::Log,
+            false => Output::Plain {
+                start: timestamp.then(Instant::now),
+            },
+        }
+    }
+}
+
+fn print_frame(
+    interface: &str,
+    frame: &Frame,
+    output: Output,
+    filters: &Filters,
+) {
+    if !filters.matches(frame) {
+        return;
+    }
+
+    match output {
+        Output::Plain { start: Some(start) } => println!(
+            "({:.6})  {}",
+            start.elapsed().as_secs_f64(),
+            frame::format(interface, frame)
+        ),
+        Output::Plain { start: None } => {
+            println!("{}", frame::format(interface, frame))
+        }
+        Output::Log => {
+            let entry = candump::Entry {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default(),
+                interface: interface.into(),
+                frame: *frame,
+            };
+            println!("{entry}");
+        }
+    }
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
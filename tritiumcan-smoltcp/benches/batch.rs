@@ -0,0 +1,234 @@
+//! Compares per-frame and batched sends and receives over a loopback
+//! connection.
+
+use std::time::{self, Duration};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use embedded_can::StandardId;
+use smoltcp::{
+    iface::{Config, Interface, SocketHandle, SocketSet},
+    phy::{Loopback, Medium},
+    socket::tcp::{Socket, SocketBuffer},
+    time::Instant,
+    wire::{EthernetAddress, IpAddress, IpCidr},
+};
+use tritiumcan_smoltcp::{
+    proto::{
+        datagram::{Frame, Packet, FRAME_LEN},
+        Bitrate, BusNumber, PORT,
+    },
+    tcp::{self, Server},
+    Received,
+};
+use zerocopy::AsBytes;
+
+const FRAMES: usize = 64;
+
+struct Loop {
+    device: Loopback,
+    iface: Interface,
+    sockets: SocketSet<'static>,
+    server: Server,
+    client: SocketHandle,
+}
+
+impl Loop {
+    fn new() -> Self {
+        let mut device = Loopback::new(Medium::Ethernet);
+        let mac_addr = EthernetAddress([0x02, 0, 0, 0, 0, 1]);
+        let mut iface =
+            Interface::new(Config::new(mac_addr.into()), &mut device, now());
+        iface.update_ip_addrs(|addrs| {
+            addrs
+                .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
+                .unwrap();
+        });
+
+        let mut sockets = SocketSet::new(vec![]);
+        let mut server = Server::new(
+            &mut sockets,
+            SocketBuffer::new(vec![0; 4096]),
+            SocketBuffer::new(vec![0; 4096]),
+            mac_addr,
+            now(),
+            BusNumber::default(),
+            Bitrate::Kbps1000,
+        );
+
+        let mut config = tcp::Config::new();
+        config.set_nagle(false);
+        server.set_config(&mut sockets, config);
+
+        let mut heartbeat = server.heartbeat_config().clone();
+        heartbeat.set_enabled(false);
+        server.set_heartbeat_config(heartbeat);
+
+        let mut client = Socket::new(
+            SocketBuffer::new(vec![0; 4096]),
+            SocketBuffer::new(vec![0; 4096]),
+        );
+        client.set_nagle_enabled(false);
+        let client = sockets.add(client);
+
+        let mut this = Self {
+            device,
+            iface,
+            sockets,
+            server,
+            client,
+        };
+
+        this.pump();
+        let socket = this.sockets.get_mut::<Socket>(this.client);
+        socket
+            .connect(
+                this.iface.context(),
+                (IpAddress::v4(127, 0, 0, 1), PORT),
+                49152,
+            )
+            .unwrap();
+
+        let header = Packet::new_stream_header(&BusNumber::default());
+        this.pump();
+        this.client().send_slice(header.as_bytes()).unwrap();
+        this.pump();
+        this.drain_client();
+
+        this
+    }
+
+    fn client(&mut self) -> &mut Socket<'static> {
+        self.sockets.get_mut::<Socket>(self.client)
+    }
+
+    /// Discard everything the client received, returning the length.
+    fn drain_client(&mut self) -> usize {
+        let mut buf = [0; 4096];
+        self.client().recv_slice(&mut buf).unwrap()
+    }
+
+    /// Run the interface and server until the loopback device is idle.
+    fn pump(&mut self) {
+        for _ in 0..8 {
+            self.iface.poll(now(), &mut self.device, &mut self.sockets);
+            self.server.poll(&mut self.sockets, now());
+        }
+    }
+}
+
+fn now() -> Instant {
+    Instant::from_millis(0)
+}
+
+fn frames() -> Vec<Frame> {
+    (0..FRAMES as u16)
+        .map(|n| {
+            let id = StandardId::new(n).unwrap();
+            <Frame as embedded_can::Frame>::new(id, &n.to_be_bytes()).unwrap()
+        })
+        .collect()
+}
+
+/// Time only the server calls, moving the data through the loopback
+/// interface between iterations.
+fn bench(
+    l: &mut Loop,
+    iters: u64,
+    mut setup: impl FnMut(&mut Loop),
+    mut routine: impl FnMut(&mut Loop),
+    mut teardown: impl FnMut(&mut Loop),
+) -> Duration {
+    let mut total = Duration::ZERO;
+
+    for _ in 0..iters {
+        setup(l);
+        let start = time::Instant::now();
+        routine(l);
+        total += start.elapsed();
+        teardown(l);
+    }
+
+    total
+}
+
+fn send(c: &mut Criterion) {
+    let mut group = c.benchmark_group("send");
+    let frames = frames();
+    let mut l = Loop::new();
+
+    let drain = |l: &mut Loop| {
+        l.pump();
+        assert_eq!(l.drain_client(), FRAMES * FRAME_LEN);
+    };
+
+    group.bench_function(BenchmarkId::new("send_frame", FRAMES), |b| {
+        b.iter_custom(|iters| {
+            let send = |l: &mut Loop| {
+                for frame in &frames {
+                    l.server.send_frame(&mut l.sockets, frame).unwrap();
+                }
+            };
+            bench(&mut l, iters, |_| {}, send, drain)
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("send_frames", FRAMES), |b| {
+        b.iter_custom(|iters| {
+            let send = |l: &mut Loop| {
+                let sent =
+                    l.server.send_frames(&mut l.sockets, &frames).unwrap();
+                assert_eq!(sent, FRAMES);
+            };
+            bench(&mut l, iters, |_| {}, send, drain)
+        })
+    });
+
+    group.finish();
+}
+
+fn recv(c: &mut Criterion) {
+    let mut group = c.benchmark_group("recv");
+    let bytes: Vec<u8> = frames()
+        .iter()
+        .flat_map(|frame| frame.as_bytes().to_vec())
+        .collect();
+    let mut l = Loop::new();
+
+    let fill = |l: &mut Loop| {
+        l.client().send_slice(&bytes).unwrap();
+        l.pump();
+    };
+
+    group.bench_function(BenchmarkId::new("recv_frame", FRAMES), |b| {
+        b.iter_custom(|iters| {
+            let recv = |l: &mut Loop| {
+                let mut count = 0;
+                while let Some(_frame) =
+                    l.server.recv_frame(&mut l.sockets).unwrap()
+                {
+                    count += 1;
+                }
+                assert_eq!(count, FRAMES);
+            };
+            bench(&mut l, iters, fill, recv, |_| {})
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("recv_frames", FRAMES), |b| {
+        let mut frames = [Received::default(); FRAMES];
+
+        b.iter_custom(|iters| {
+            let recv = |l: &mut Loop| {
+                let count =
+                    l.server.recv_frames(&mut l.sockets, &mut frames).unwrap();
+                assert_eq!(count, FRAMES);
+            };
+            bench(&mut l, iters, fill, recv, |_| {})
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, send, recv);
+criterion_main!(benches);
@@ -0,0 +1,224 @@
+//! Heartbeat configuration.
+
+use smoltcp::{
+    time::{Duration, Instant},
+    wire::IpEndpoint,
+};
+use tritiumcan::HEARTBEAT_INTERVAL;
+
+/// Maximum number of unicast heartbeat destinations.
+pub const MAX_DESTINATIONS: usize = 4;
+
+/// Per-server heartbeat configuration.
+///
+/// Destinations only apply to the UDP server, the TCP server always sends
+/// heartbeats on the open connection.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Config {
+    enabled: bool,
+    interval: Duration,
+    jitter: Duration,
+    multicast: bool,
+    destinations: [Option<IpEndpoint>; MAX_DESTINATIONS],
+}
+
+impl Config {
+    /// Send a heartbeat to the multicast group every [`HEARTBEAT_INTERVAL`].
+    pub const fn new() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_micros(
+                HEARTBEAT_INTERVAL.as_micros() as u64
+            ),
+            jitter: Duration::ZERO,
+            multicast: true,
+            destinations: [None; MAX_DESTINATIONS],
+        }
+    }
+
+    /// Whether heartbeats are sent.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable heartbeats, disable for passive listeners.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Time between heartbeats.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Set the time between heartbeats.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Maximum random delay added to each interval.
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Set the maximum random delay added to each interval, to avoid adapters
+    /// sending heartbeats in synchronised bursts.
+    pub fn set_jitter(&mut self, jitter: Duration) {
+        self.jitter = jitter;
+    }
+
+    /// Whether heartbeats are sent to the multicast group.
+    pub fn multicast(&self) -> bool {
+        self.multicast
+    }
+
+    /// Enable or disable sending heartbeats to the multicast group.
+    pub fn set_multicast(&mut self, multicast: bool) {
+        self.multicast = multicast;
+    }
+
+    /// Unicast destinations heartbeats are sent to.
+    pub fn destinations(&self) -> impl Iterator<Item = IpEndpoint> + '_ {
+        self.destinations.iter().flatten().copied()
+    }
+
+    /// Add a unicast destination, returning `false` if the list is full.
+    pub fn add_destination(&mut self, endpoint: IpEndpoint) -> bool {
+        match self.destinations.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(endpoint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove all unicast destinations.
+    pub fn clear_destinations(&mut self) {
+        self.destinations = [None; MAX_DESTINATIONS];
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Heartbeat deadline tracking.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub(crate) struct Timer {
+    last: Instant,
+    offset: Duration,
+    rng: u32,
+}
+
+impl Timer {
+    /// Start the first interval at `now`, seeding the jitter from `seed` so
+    /// that adapters started together do not stay in step.
+    pub(crate) fn new(now: Instant, seed: &[u8; 6]) -> Self {
+        let rng = seed
+            .iter()
+            .fold(0x9E37_79B9u32, |acc, &b| acc.rotate_left(5) ^ b as u32);
+
+        Self {
+            last: now,
+            offset: Duration::ZERO,
+            // xorshift must not be seeded with zero
+            rng: rng.max(1),
+        }
+    }
+
+    /// Time the next heartbeat is due, `None` if heartbeats are disabled.
+    pub(crate) fn next(&self, config: &Config) -> Option<Instant> {
+        if !config.enabled {
+            return None;
+        }
+
+        Some(self.last + config.interval + self.offset.min(config.jitter))
+    }
+
+    pub(crate) fn is_due(&self, config: &Config, now: Instant) -> bool {
+        self.next(config).is_some_and(|next| now >= next)
+    }
+
+    /// Start a new interval after a heartbeat was sent at `now`.
+    pub(crate) fn reset(&mut self, config: &Config, now: Instant) {
+        self.last = now;
+
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+
+        let jitter = config.jitter.total_micros();
+        self.offset = match jitter {
+            0 => Duration::ZERO,
+            _ => Duration::from_micros(self.rng as u64 % (jitter + 1)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval() {
+        let config = Config::new();
+        let mut timer = Timer::new(Instant::from_millis(0), &[0; 6]);
+
+        assert!(!timer.is_due(&config, Instant::from_millis(999)));
+        assert!(timer.is_due(&config, Instant::from_millis(1000)));
+
+        timer.reset(&config, Instant::from_millis(1000));
+        assert_eq!(timer.next(&config), Some(Instant::from_millis(2000)));
+    }
+
+    #[test]
+    fn jitter() {
+        let mut config = Config::new();
+        config.set_jitter(Duration::from_millis(100));
+        let mut timer =
+            Timer::new(Instant::from_millis(0), &[2, 0, 0, 0, 0, 1]);
+
+        let mut offsets = [0; 16];
+        for (n, offset) in offsets.iter_mut().enumerate() {
+            let now = Instant::from_secs(n as i64);
+            timer.reset(&config, now);
+
+            let next = timer.next(&config).unwrap();
+            *offset = (next - now - config.interval()).total_millis();
+            assert!(*offset <= 100);
+        }
+
+        assert!(offsets.iter().any(|&offset| offset != offsets[0]));
+    }
+
+    #[test]
+    fn disabled() {
+        let mut config = Config::new();
+        config.set_enabled(false);
+        let timer = Timer::new(Instant::from_millis(0), &[0; 6]);
+
+        assert_eq!(timer.next(&config), None);
+        assert!(!timer.is_due(&config, Instant::from_secs(1000)));
+    }
+
+    #[test]
+    fn destinations() {
+        let mut config = Config::new();
+        let endpoint = IpEndpoint::new(crate::BROADCAST, 1);
+
+        for _ in 0..MAX_DESTINATIONS {
+            assert!(config.add_destination(endpoint));
+        }
+        assert!(!config.add_destination(endpoint));
+        assert_eq!(config.destinations().count(), MAX_DESTINATIONS);
+
+        config.clear_destinations();
+        assert_eq!(config.destinations().count(), 0);
+    }
+}
@@ -8,8 +8,9 @@
 //! - `async` enable the async feature for `smoltcp` and the associated methods.
 //! - `defmt-03` enable defmt formatting attributes.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
+pub mod heartbeat;
 pub mod tcp;
 pub mod udp;
 
@@ -17,7 +18,129 @@ pub mod udp;
 pub use tritiumcan as proto;
 
 use core::net::Ipv4Addr;
-use smoltcp::wire::IpAddress;
+use smoltcp::{time::Instant, wire::IpAddress};
+use tritiumcan::datagram::Frame;
+
+/// Maximum number of acceptance rules per direction.
+pub const MAX_FILTERS: usize = 8;
+
+/// Acceptance filters, applied by each server to received and transmitted
+/// frames.
+pub type Filters = tritiumcan::filter::Filters<MAX_FILTERS>;
+
+/// Number of frames held by each server's transmit queue.
+pub const TX_QUEUE_LEN: usize = 16;
+
+pub(crate) type TxQueue = tritiumcan::queue::Queue<TX_QUEUE_LEN>;
+
+/// Number of transmitted frames each server holds for local echo.
+pub const ECHO_QUEUE_LEN: usize = 16;
+
+/// Server traffic counters.
+///
+/// Byte counts include protocol headers and heartbeats.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Stats {
+    /// CAN frames sent.
+    pub frames_tx: u64,
+    /// CAN frames received and handed to the caller.
+    pub frames_rx: u64,
+    /// Bytes sent.
+    pub bytes_tx: u64,
+    /// Bytes received.
+    pub bytes_rx: u64,
+    /// Heartbeats sent.
+    pub heartbeats_tx: u64,
+    /// Frames and heartbeats that could not be sent.
+    pub send_errors: u64,
+    /// Packets or frames with an invalid length, identifier or data length.
+    pub malformed: u64,
+    /// Packets or stream headers with an unexpected protocol version.
+    pub wrong_version: u64,
+    /// UDP frames addressed to a different bus, which are dropped.
+    pub wrong_bus: u64,
+    /// TCP clients connected after an earlier connection.
+    pub reconnects: u64,
+}
+
+/// A frame received by a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Received {
+    /// Time passed to the last server poll before the frame was dequeued.
+    ///
+    /// Servers are polled right after the interface, so this is the time the
+    /// datagram or TCP segment holding the frame was read from the network,
+    /// to within one poll interval.
+    pub timestamp: Instant,
+    pub frame: Frame,
+    /// Whether this is a local echo of a frame the server sent, rather than
+    /// a frame received from the network.
+    pub echoed: bool,
+}
+
+impl Default for Received {
+    fn default() -> Self {
+        Self {
+            timestamp: Instant::ZERO,
+            frame: Frame::default(),
+            echoed: false,
+        }
+    }
+}
+
+/// Sent frames waiting to be echoed, oldest first.
+///
+/// When full the oldest echo is dropped, as a receive FIFO would overrun.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub(crate) struct EchoQueue {
+    frames: [Received; ECHO_QUEUE_LEN],
+    start: usize,
+    len: usize,
+}
+
+impl EchoQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            frames: [Received::default(); ECHO_QUEUE_LEN],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, timestamp: Instant, frame: Frame) {
+        let end = (self.start + self.len) % ECHO_QUEUE_LEN;
+        self.frames[end] = Received {
+            timestamp,
+            frame,
+            echoed: true,
+        };
+
+        if self.len == ECHO_QUEUE_LEN {
+            self.start = (self.start + 1) % ECHO_QUEUE_LEN;
+        } else {
+            self.len += 1;
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Received> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let frame = self.frames[self.start];
+        self.start = (self.start + 1) % ECHO_QUEUE_LEN;
+        self.len -= 1;
+
+        Some(frame)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+}
 
 // const conversion between different libray types
 
@@ -1,18 +1,103 @@
 //! TCP protocol.
 
-use core::mem::size_of;
-
+use crate::heartbeat::{self, Timer};
+use crate::{EchoQueue, Filters, Received, Stats, TxQueue};
 use smoltcp::{
     iface::{SocketHandle, SocketSet},
     socket::tcp::{RecvError, SendError, Socket, SocketBuffer, State},
     time::{Duration, Instant},
-    wire::EthernetAddress,
+    wire::{EthernetAddress, IpEndpoint},
 };
 use tritiumcan::{
-    datagram::{Frame, Header, Packet},
-    BusNumber, HEARTBEAT_INTERVAL, PORT, PROTOCOL_VERSION,
+    datagram::{DecodeError, Frame, Header, Message, Packet, FRAME_LEN},
+    stream::{Decoder, HEADER_LEN},
+    Bitrate, BusNumber, PORT, PROTOCOL_VERSION,
 };
-use zerocopy::{AsBytes, FromZeroes};
+use zerocopy::{AsBytes, FromBytes, FromZeroes};
+
+/// Connection configuration.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Config {
+    timeout: Option<Duration>,
+    keep_alive: Option<Duration>,
+    nagle: bool,
+}
+
+impl Config {
+    /// Abort connections after 3 seconds without a response, with no
+    /// keep-alive and Nagle's algorithm enabled.
+    pub const fn new() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(3)),
+            keep_alive: None,
+            nagle: true,
+        }
+    }
+
+    /// Time without a response from the client before the connection is
+    /// aborted.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Set the time without a response from the client before the connection
+    /// is aborted, `None` waits indefinitely.
+    ///
+    /// Without keep-alive an idle connection is only timed out while there is
+    /// unacknowledged data to send.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Interval between keep-alive packets.
+    pub fn keep_alive(&self) -> Option<Duration> {
+        self.keep_alive
+    }
+
+    /// Set the interval between keep-alive packets sent on an idle
+    /// connection, `None` disables keep-alive.
+    pub fn set_keep_alive(&mut self, interval: Option<Duration>) {
+        self.keep_alive = interval;
+    }
+
+    /// Whether Nagle's algorithm is enabled.
+    pub fn nagle(&self) -> bool {
+        self.nagle
+    }
+
+    /// Enable or disable Nagle's algorithm, disable to send frames without
+    /// waiting for outstanding data to be acknowledged.
+    pub fn set_nagle(&mut self, enabled: bool) {
+        self.nagle = enabled;
+    }
+
+    fn apply(&self, socket: &mut Socket) {
+        socket.set_timeout(self.timeout);
+        socket.set_keep_alive(self.keep_alive);
+        socket.set_nagle_enabled(self.nagle);
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Connection lifecycle event returned by [`Server::poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Event {
+    /// A client connected.
+    ClientConnected { remote: IpEndpoint },
+    /// The connected client went away, or the connection was reset or timed
+    /// out.
+    ClientDisconnected,
+    /// The client sent an invalid stream header and the connection was
+    /// aborted. Followed by [`Event::ClientDisconnected`].
+    HandshakeFailed,
+}
 
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
@@ -21,12 +106,25 @@ pub struct Server {
     handle: SocketHandle,
     mac_addr: [u8; 6],
     bus_number: BusNumber,
-    data_rate: u16,
+    data_rate: Bitrate,
+    heartbeat: heartbeat::Config,
+    config: Config,
+    rx_filters: Filters,
+    tx_filters: Filters,
+    tx_queue_enabled: bool,
+    echo_enabled: bool,
+    reflect_enabled: bool,
 
     // state
-    last_heartbeat: Instant,
+    heartbeat_timer: Timer,
+    last_poll: Instant,
+    remote: Option<IpEndpoint>,
     tx_start: bool,
-    rx_start: bool,
+    decoder: Decoder,
+    tx_queue: TxQueue,
+    echo: EchoQueue,
+    connected_before: bool,
+    stats: Stats,
 }
 
 impl Server {
@@ -37,41 +135,294 @@ impl Server {
         mac_addr: EthernetAddress,
         now: Instant,
         bus_number: BusNumber,
-        data_rate: u16,
+        data_rate: Bitrate,
     ) -> Self {
+        let config = Config::new();
         let mut socket = Socket::new(rx_buffer, tx_buffer);
-        socket.set_timeout(Some(Duration::from_secs(3)));
+        config.apply(&mut socket);
         let handle = sockets.add(socket);
 
         Self {
             handle,
             mac_addr: mac_addr.0,
-            last_heartbeat: now,
             bus_number,
             data_rate,
+            heartbeat: heartbeat::Config::new(),
+            config,
+            rx_filters: Filters::new(),
+            tx_filters: Filters::new(),
+            tx_queue_enabled: false,
+            echo_enabled: false,
+            reflect_enabled: false,
+            heartbeat_timer: Timer::new(now, &mac_addr.0),
+            last_poll: now,
+            remote: None,
             tx_start: false,
-            rx_start: false,
+            decoder: Decoder::new(),
+            tx_queue: TxQueue::new(),
+            echo: EchoQueue::new(),
+            connected_before: false,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Get the current bus number.
+    pub fn bus_number(&self) -> BusNumber {
+        self.bus_number
+    }
+
+    /// Set a new bus number.
+    ///
+    /// The bus number is announced in the stream header, which can only be
+    /// sent at the start of a stream: clients decode everything after the
+    /// first header as frames, so a second header would corrupt the stream.
+    /// Instead of re-sending the header to a connected client, the
+    /// connection is closed, and the client receives a header with the new
+    /// bus number once it reconnects.
+    pub fn set_bus_number(
+        &mut self,
+        sockets: &mut SocketSet,
+        bus_number: BusNumber,
+    ) {
+        if bus_number == self.bus_number {
+            return;
+        }
+
+        self.bus_number = bus_number;
+
+        if self.tx_start {
+            #[cfg(feature = "defmt-03")]
+            defmt::info!("Bus number changed, restarting connection");
+            self.close(sockets);
+        }
+    }
+
+    /// Get the bitrate announced in heartbeats.
+    pub fn data_rate(&self) -> Bitrate {
+        self.data_rate
+    }
+
+    /// Set the bitrate announced in heartbeats.
+    pub fn set_data_rate(&mut self, data_rate: Bitrate) {
+        self.data_rate = data_rate;
+    }
+
+    /// Get the MAC address announced in heartbeats.
+    pub fn mac_addr(&self) -> EthernetAddress {
+        EthernetAddress(self.mac_addr)
+    }
+
+    /// Set the MAC address announced in heartbeats.
+    pub fn set_mac_addr(&mut self, mac_addr: EthernetAddress) {
+        self.mac_addr = mac_addr.0;
+    }
+
+    /// Get the filters applied to frames received from the network.
+    pub fn rx_filters(&self) -> &Filters {
+        &self.rx_filters
+    }
+
+    /// Set the filters applied to frames received from the network, frames
+    /// that do not match are dropped by [`Server::recv_frame`].
+    pub fn set_rx_filters(&mut self, filters: Filters) {
+        self.rx_filters = filters;
+    }
+
+    /// Get the filters applied to frames sent to the network.
+    pub fn tx_filters(&self) -> &Filters {
+        &self.tx_filters
+    }
+
+    /// Set the filters applied to frames sent to the network, frames that do
+    /// not match are silently dropped by [`Server::send_frame`].
+    pub fn set_tx_filters(&mut self, filters: Filters) {
+        self.tx_filters = filters;
+    }
+
+    /// Whether frames are queued when the socket cannot take them.
+    pub fn is_tx_queue_enabled(&self) -> bool {
+        self.tx_queue_enabled
+    }
+
+    /// Enable or disable the transmit queue.
+    ///
+    /// When enabled, [`Server::send_frame`] holds up to
+    /// [`TX_QUEUE_LEN`](crate::TX_QUEUE_LEN) frames and [`Server::poll`] sends
+    /// them lowest CAN ID first, as the bus would arbitrate them. Queued
+    /// frames are dropped when the client disconnects.
+    pub fn set_tx_queue_enabled(&mut self, enabled: bool) {
+        self.tx_queue_enabled = enabled;
+    }
+
+    /// Number of frames waiting in the transmit queue.
+    pub fn tx_queue_len(&self) -> usize {
+        self.tx_queue.len()
+    }
+
+    /// Whether sent frames are echoed back to the receive methods.
+    pub fn is_echo_enabled(&self) -> bool {
+        self.echo_enabled
+    }
+
+    /// Enable or disable local echo.
+    ///
+    /// When enabled, each frame written to the socket is also returned by
+    /// the receive methods with [`Received::echoed`] set, holding up to
+    /// [`ECHO_QUEUE_LEN`](crate::ECHO_QUEUE_LEN) echoes. Disabling drops any
+    /// echoes not yet received.
+    pub fn set_echo_enabled(&mut self, enabled: bool) {
+        self.echo_enabled = enabled;
+
+        if !enabled {
+            self.echo.clear();
         }
     }
 
-    pub fn poll(&mut self, sockets: &mut SocketSet, now: Instant) {
+    /// Whether received frames are sent back to the client.
+    pub fn is_reflect_enabled(&self) -> bool {
+        self.reflect_enabled
+    }
+
+    /// Enable or disable reflecting received frames back to the client for
+    /// link testing.
+    ///
+    /// Frames are reflected as they are received, bypassing the transmit
+    /// filters and queue.
+    pub fn set_reflect_enabled(&mut self, enabled: bool) {
+        self.reflect_enabled = enabled;
+    }
+
+    /// Get the heartbeat configuration.
+    pub fn heartbeat_config(&self) -> &heartbeat::Config {
+        &self.heartbeat
+    }
+
+    /// Set a new heartbeat configuration, taking effect from the current
+    /// interval.
+    ///
+    /// Heartbeat destinations are ignored, heartbeats are always sent on the
+    /// open connection.
+    pub fn set_heartbeat_config(&mut self, config: heartbeat::Config) {
+        self.heartbeat = config;
+    }
+
+    /// Get the connection configuration.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set a new connection configuration, also applied to an open
+    /// connection.
+    pub fn set_config(&mut self, sockets: &mut SocketSet, config: Config) {
         let socket = sockets.get_mut::<Socket>(self.handle);
+        config.apply(socket);
 
-        if !socket.is_open() {
-            if !socket.is_listening() {
-                if let Err(_err) = socket.listen(PORT) {
-                    #[cfg(feature = "defmt-03")]
-                    defmt::error!("Failed to bind to {}: {}", PORT, _err);
-                }
+        self.config = config;
+    }
+
+    /// Close the connection after all queued data has been sent.
+    ///
+    /// [`Event::ClientDisconnected`] is returned by [`Server::poll`] once the
+    /// connection has closed, after which the server listens again.
+    pub fn close(&mut self, sockets: &mut SocketSet) {
+        sockets.get_mut::<Socket>(self.handle).close();
+    }
+
+    /// Reset the connection immediately, dropping any queued data.
+    ///
+    /// [`Event::ClientDisconnected`] is returned by the next
+    /// [`Server::poll`].
+    pub fn abort(&mut self, sockets: &mut SocketSet) {
+        sockets.get_mut::<Socket>(self.handle).abort();
+    }
+
+    /// Address of the connected client.
+    pub fn remote(&self) -> Option<IpEndpoint> {
+        self.remote
+    }
+
+    /// Traffic counters since the server was created or last reset.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Reset all traffic counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Perform bufferred transactions and send heartbeat if needed.
+    ///
+    /// Returns at most one connection lifecycle event per call. Call after
+    /// each interface poll and again by the time returned from
+    /// [`Server::poll_at`].
+    pub fn poll(
+        &mut self,
+        sockets: &mut SocketSet,
+        now: Instant,
+    ) -> Option<Event> {
+        let socket = sockets.get_mut::<Socket>(self.handle);
+        self.last_poll = now;
+
+        if !socket.is_open() && !socket.is_listening() {
+            if let Err(_err) = socket.listen(PORT) {
+                #[cfg(feature = "defmt-03")]
+                defmt::error!("Failed to bind to {}: {}", PORT, _err);
             }
         }
 
         // if client closes, close on our end as well
         if socket.state() == State::CloseWait {
             socket.close();
-            self.tx_start = false;
-            self.rx_start = false;
-            return;
+            return self.disconnected();
+        }
+
+        if self.remote.is_some() && !socket.is_active() {
+            return self.disconnected();
+        }
+
+        if self.remote.is_none() && socket.state() == State::Established {
+            self.remote = socket.remote_endpoint();
+
+            if self.remote.is_some() {
+                if self.connected_before {
+                    self.stats.reconnects += 1;
+                }
+                self.connected_before = true;
+            }
+
+            return self.remote.map(|remote| Event::ClientConnected { remote });
+        }
+
+        if self.decoder.bus_number().is_none()
+            && socket.recv_queue() >= HEADER_LEN
+        {
+            let mut header = [0; HEADER_LEN];
+            let valid = match socket.recv_slice(&mut header) {
+                Ok(len) => {
+                    self.stats.bytes_rx += len as u64;
+
+                    match self.decoder.decode(&header) {
+                        Some((_, Ok(_))) => true,
+                        Some((_, Err(DecodeError::Version))) => {
+                            self.stats.wrong_version += 1;
+                            false
+                        }
+                        _ => {
+                            self.stats.malformed += 1;
+                            false
+                        }
+                    }
+                }
+                Err(_) => false,
+            };
+
+            if !valid {
+                #[cfg(feature = "defmt-03")]
+                defmt::warn!("Invalid stream header, aborting connection");
+                socket.abort();
+                return Some(Event::HandshakeFailed);
+            }
         }
 
         if socket.can_send() {
@@ -84,23 +435,84 @@ impl Server {
                 packet.header.set_bus_number(self.bus_number.into());
                 packet.header.set_client_identifier(0);
 
-                if socket.send_slice(packet.as_bytes()).is_ok() {
+                // retried on the next poll until the whole header fits
+                if has_room(socket, HEADER_LEN)
+                    && socket.send_slice(packet.as_bytes()).is_ok()
+                {
                     self.tx_start = true;
+                    self.stats.bytes_tx += HEADER_LEN as u64;
                 }
             }
 
-            if now - self.last_heartbeat > HEARTBEAT_INTERVAL.into() {
+            if self.heartbeat_timer.is_due(&self.heartbeat, now) {
                 match self.write_heartbeat(socket) {
-                    Ok(_) => self.last_heartbeat = now,
+                    Ok(_) => self.heartbeat_timer.reset(&self.heartbeat, now),
                     Err(_err) => {
                         #[cfg(feature = "defmt-03")]
                         defmt::error!("Failed to send heartbeat: {}", _err);
                     }
                 }
             }
+
+            self.drain_tx_queue(socket);
+        }
+
+        None
+    }
+
+    /// Time [`Server::poll`] next needs to be called, `None` if it only needs
+    /// calling after the interface received packets.
+    ///
+    /// Returns `now` while there is work pending, such as a connection event
+    /// to report, so that a device can sleep until the earlier of this and
+    /// [`Interface::poll_at`].
+    ///
+    /// [`Interface::poll_at`]: smoltcp::iface::Interface::poll_at
+    pub fn poll_at(
+        &self,
+        sockets: &SocketSet,
+        now: Instant,
+    ) -> Option<Instant> {
+        let socket = sockets.get::<Socket>(self.handle);
+
+        let pending = (!socket.is_open() && !socket.is_listening())
+            || socket.state() == State::CloseWait
+            || self.remote.is_some() && !socket.is_active()
+            || self.remote.is_none() && socket.state() == State::Established
+            || self.decoder.bus_number().is_none()
+                && socket.recv_queue() >= HEADER_LEN
+            || socket.can_send()
+                && (!self.tx_start || !self.tx_queue.is_empty());
+
+        if pending {
+            return Some(now);
+        }
+
+        // heartbeats are only sent on an open connection
+        self.remote
+            .and_then(|_| self.heartbeat_timer.next(&self.heartbeat))
+    }
+
+    fn drain_tx_queue(&mut self, socket: &mut Socket) {
+        while let Some(&frame) = self.tx_queue.peek() {
+            if self.write_frame(socket, &frame).is_err() {
+                break;
+            }
+
+            self.tx_queue.pop();
         }
     }
 
+    /// Reset the connection state, reporting a disconnect if a client was
+    /// connected.
+    fn disconnected(&mut self) -> Option<Event> {
+        self.tx_start = false;
+        self.decoder = Decoder::new();
+        self.tx_queue.clear();
+
+        self.remote.take().map(|_| Event::ClientDisconnected)
+    }
+
     /// Send heartbeat.
     ///
     /// Note: this doesn't reset the heartbeat interval.
@@ -113,57 +525,323 @@ impl Server {
         self.write_heartbeat(socket)
     }
 
-    fn write_heartbeat(&self, socket: &mut Socket) -> Result<(), SendError> {
+    fn write_heartbeat(
+        &mut self,
+        socket: &mut Socket,
+    ) -> Result<(), SendError> {
         let packet = Packet::new_heartbeat(
             &self.mac_addr,
             &self.bus_number,
             &self.data_rate,
         );
 
-        socket.send_slice(&packet.frame.0).map(|_| ())
+        // a partial heartbeat would break the stream framing
+        let result = if self.tx_start && has_room(socket, FRAME_LEN) {
+            socket.send_slice(&packet.frame.0)
+        } else {
+            Err(SendError::InvalidState)
+        };
+
+        match result {
+            Ok(len) => {
+                self.stats.heartbeats_tx += 1;
+                self.stats.bytes_tx += len as u64;
+                Ok(())
+            }
+            Err(err) => {
+                self.stats.send_errors += 1;
+                Err(err)
+            }
+        }
     }
 
     /// Send a CAN frame.
+    ///
+    /// Fails if no client is connected or, with the transmit queue disabled,
+    /// if the socket has no room for the frame. With the queue enabled, a full
+    /// queue also returns [`SendError::InvalidState`]; compare
+    /// [`Server::tx_queue_len`] with [`TX_QUEUE_LEN`](crate::TX_QUEUE_LEN) to
+    /// tell the two apart.
+    ///
+    /// Frames with more than 8 data bytes are dropped and counted as send
+    /// errors, as in [`Server::send_frames`].
     pub fn send_frame(
         &mut self,
         sockets: &mut SocketSet,
         frame: &impl embedded_can::Frame,
     ) -> Result<(), SendError> {
+        if !self.tx_filters.matches(frame) {
+            return Ok(());
+        }
+
+        let socket = sockets.get_mut::<Socket>(self.handle);
+
+        let Some(frame) = Frame::from_frame(frame) else {
+            self.stats.send_errors += 1;
+            return Ok(());
+        };
+
+        let result = if self.tx_queue_enabled && self.tx_start {
+            self.tx_queue
+                .push(frame)
+                .map(|_| self.drain_tx_queue(socket))
+                .map_err(|_| SendError::InvalidState)
+        } else {
+            self.write_frame(socket, &frame)
+        };
+
+        if result.is_err() {
+            self.stats.send_errors += 1;
+        }
+
+        result
+    }
+
+    /// Send several CAN frames, packing as many as fit into each socket
+    /// write.
+    ///
+    /// Returns the number of frames taken from the start of `frames`, frames
+    /// rejected by the transmit filters count as taken. Fewer than
+    /// `frames.len()` are taken once the socket, or the transmit queue when
+    /// enabled, is full. Frames with more than 8 data bytes are skipped and
+    /// counted as send errors.
+    pub fn send_frames<F: embedded_can::Frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        frames: &[F],
+    ) -> Result<usize, SendError> {
         let socket = sockets.get_mut::<Socket>(self.handle);
 
-        let frame = Frame::from_frame(frame).unwrap();
+        if !self.tx_start {
+            self.stats.send_errors += 1;
+            return Err(SendError::InvalidState);
+        }
+
+        if self.tx_queue_enabled {
+            let mut taken = 0;
+
+            for frame in frames {
+                if self.tx_filters.matches(frame) {
+                    let Some(frame) = Frame::from_frame(frame) else {
+                        self.stats.send_errors += 1;
+                        taken += 1;
+                        continue;
+                    };
+                    if !self.enqueue(socket, frame) {
+                        self.stats.send_errors += 1;
+                        break;
+                    }
+                }
+                taken += 1;
+            }
+
+            self.drain_tx_queue(socket);
+            return Ok(taken);
+        }
+
+        let mut sent = 0;
+
+        while sent < frames.len() {
+            let result = socket.send(|buf| {
+                let mut len = 0;
+                let mut taken = 0;
+
+                for frame in &frames[sent..] {
+                    if self.tx_filters.matches(frame) {
+                        let Some(frame) = Frame::from_frame(frame) else {
+                            self.stats.send_errors += 1;
+                            taken += 1;
+                            continue;
+                        };
+                        let Some(chunk) = buf.get_mut(len..len + FRAME_LEN)
+                        else {
+                            break;
+                        };
+
+                        chunk.copy_from_slice(frame.as_bytes());
+                        len += FRAME_LEN;
+
+                        if self.echo_enabled {
+                            self.echo.push(self.last_poll, frame);
+                        }
+                    }
+                    taken += 1;
+                }
+
+                (len, (len, taken))
+            });
+
+            let (len, taken) = match result {
+                Ok(written) => written,
+                Err(err) => {
+                    self.stats.send_errors += 1;
+                    return Err(err);
+                }
+            };
+            sent += taken;
+            self.stats.frames_tx += (len / FRAME_LEN) as u64;
+            self.stats.bytes_tx += len as u64;
+
+            if taken == 0 {
+                // the free space wraps around the end of the ring buffer
+                let Some(frame) = Frame::from_frame(&frames[sent]) else {
+                    self.stats.send_errors += 1;
+                    sent += 1;
+                    continue;
+                };
+                if self.write_frame(socket, &frame).is_err() {
+                    self.stats.send_errors += 1;
+                    break;
+                }
+                sent += 1;
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Queue a frame, making room by sending queued frames if needed.
+    fn enqueue(&mut self, socket: &mut Socket, frame: Frame) -> bool {
+        if self.tx_queue.push(frame).is_ok() {
+            return true;
+        }
+
+        self.drain_tx_queue(socket);
+        self.tx_queue.push(frame).is_ok()
+    }
+
+    /// Write a whole frame to the socket, a partial frame would break the
+    /// stream framing.
+    fn write_frame(
+        &mut self,
+        socket: &mut Socket,
+        frame: &Frame,
+    ) -> Result<(), SendError> {
+        if !self.tx_start || !has_room(socket, FRAME_LEN) {
+            return Err(SendError::InvalidState);
+        }
+
+        socket.send_slice(frame.as_bytes())?;
+        self.stats.frames_tx += 1;
+        self.stats.bytes_tx += FRAME_LEN as u64;
 
-        if socket.can_send() && self.tx_start {
-            socket.send_slice(frame.as_bytes()).map(|_| ())?
+        if self.echo_enabled {
+            self.echo.push(self.last_poll, *frame);
         }
 
-        Err(SendError::InvalidState)
+        Ok(())
     }
 
     /// Receive a CAN frame.
+    ///
+    /// Returns `None` until the client's stream header has been accepted by
+    /// [`Server::poll`]. Heartbeats, malformed frames and frames rejected by
+    /// the receive filters are skipped. Pending local echoes are returned
+    /// before frames from the client.
     pub fn recv_frame(
         &mut self,
         sockets: &mut SocketSet,
-    ) -> Result<Option<Frame>, RecvError> {
+    ) -> Result<Option<Received>, RecvError> {
+        let mut frames = [Received::default()];
+
+        match self.recv_frames(sockets, &mut frames)? {
+            0 => Ok(None),
+            _ => Ok(Some(frames[0])),
+        }
+    }
+
+    /// Receive all available CAN frames, up to `frames.len()`.
+    ///
+    /// Returns the number of frames written to the start of `frames`, see
+    /// [`Server::recv_frame`] for which frames are skipped.
+    pub fn recv_frames(
+        &mut self,
+        sockets: &mut SocketSet,
+        frames: &mut [Received],
+    ) -> Result<usize, RecvError> {
         let socket = sockets.get_mut::<Socket>(self.handle);
+        let mut count = 0;
 
-        if socket.can_recv() {
-            if !self.rx_start {
-                socket.recv_slice(&mut [0; 30]).ok();
-                self.rx_start = true;
-            }
-        } else {
-            return Ok(None);
+        while count < frames.len() {
+            let Some(echo) = self.echo.pop() else {
+                break;
+            };
+            frames[count] = echo;
+            count += 1;
         }
 
-        let mut frame = Frame::new_zeroed();
-        let len = socket.recv_slice(frame.as_bytes_mut())?;
+        let Some(bus_number) = self.decoder.bus_number() else {
+            return Ok(count);
+        };
 
-        if len != size_of::<Frame>() {
-            Ok(None)
-        } else {
-            Ok(Some(frame))
+        let filters = &self.rx_filters;
+        let stats = &mut self.stats;
+        let timestamp = self.last_poll;
+        let mut accept = |frame| {
+            stats.bytes_rx += FRAME_LEN as u64;
+
+            match Message::from_frame(frame, bus_number) {
+                Ok(Message::Frame(frame)) if filters.matches(&frame) => {
+                    stats.frames_rx += 1;
+                    Some(Received {
+                        timestamp,
+                        frame,
+                        echoed: false,
+                    })
+                }
+                Ok(_) => None,
+                Err(_) => {
+                    stats.malformed += 1;
+                    None
+                }
+            }
+        };
+
+        let echoes = count;
+
+        while count < frames.len() && socket.recv_queue() >= FRAME_LEN {
+            let read = socket.recv(|buf| {
+                let mut len = 0;
+
+                for chunk in buf.chunks_exact(FRAME_LEN) {
+                    if count == frames.len() {
+                        break;
+                    }
+                    len += FRAME_LEN;
+
+                    // chunk is exactly one frame long
+                    if let Some(frame) =
+                        accept(Frame::read_from(chunk).unwrap())
+                    {
+                        frames[count] = frame;
+                        count += 1;
+                    }
+                }
+
+                (len, len)
+            })?;
+
+            if read == 0 {
+                // the next frame wraps around the end of the ring buffer
+                let mut frame = Frame::new_zeroed();
+                socket.recv_slice(frame.as_bytes_mut())?;
+
+                if let Some(frame) = accept(frame) {
+                    frames[count] = frame;
+                    count += 1;
+                }
+            }
+        }
+
+        if self.reflect_enabled {
+            for received in &frames[echoes..count] {
+                if self.write_frame(socket, &received.frame).is_err() {
+                    self.stats.send_errors += 1;
+                }
+            }
         }
+
+        Ok(count)
     }
 
     /// Register a waker for receive operations.
@@ -196,3 +874,130 @@ impl Server {
         socket.register_send_waker(waker);
     }
 }
+
+/// Whether `len` bytes can be queued on the socket in one write.
+fn has_room(socket: &Socket, len: usize) -> bool {
+    socket.can_send() && socket.send_capacity() - socket.send_queue() >= len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use smoltcp::iface::SocketStorage;
+
+    /// Create a server with 64 byte socket buffers.
+    fn server<'a>(
+        storage: &'a mut [SocketStorage<'a>; 1],
+        buffer: &'a mut [u8; 128],
+    ) -> (SocketSet<'a>, Server) {
+        let mut sockets = SocketSet::new(&mut storage[..]);
+        let (rx, tx) = buffer.split_at_mut(64);
+
+        let server = Server::new(
+            &mut sockets,
+            SocketBuffer::new(rx),
+            SocketBuffer::new(tx),
+            EthernetAddress([0x02, 0, 0, 0, 0, 1]),
+            Instant::ZERO,
+            BusNumber::default(),
+            Bitrate::Kbps500,
+        );
+
+        (sockets, server)
+    }
+
+    #[test]
+    fn config() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut buffer = [0; 128];
+        let (mut sockets, mut server) = server(&mut storage, &mut buffer);
+
+        let socket = sockets.get::<Socket>(server.handle);
+        assert_eq!(socket.timeout(), Some(Duration::from_secs(3)));
+        assert_eq!(socket.keep_alive(), None);
+        assert!(socket.nagle_enabled());
+
+        let mut config = Config::new();
+        config.set_timeout(Some(Duration::from_secs(10)));
+        config.set_keep_alive(Some(Duration::from_secs(1)));
+        config.set_nagle(false);
+        server.set_config(&mut sockets, config);
+
+        let socket = sockets.get::<Socket>(server.handle);
+        assert_eq!(socket.timeout(), Some(Duration::from_secs(10)));
+        assert_eq!(socket.keep_alive(), Some(Duration::from_secs(1)));
+        assert!(!socket.nagle_enabled());
+    }
+
+    #[test]
+    fn abort() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut buffer = [0; 128];
+        let (mut sockets, mut server) = server(&mut storage, &mut buffer);
+
+        assert_eq!(server.poll(&mut sockets, Instant::ZERO), None);
+        assert!(sockets.get::<Socket>(server.handle).is_listening());
+
+        // aborting without a client only stops listening until the next poll
+        server.abort(&mut sockets);
+        assert_eq!(server.poll(&mut sockets, Instant::ZERO), None);
+        assert!(sockets.get::<Socket>(server.handle).is_listening());
+    }
+
+    #[test]
+    fn reconfigure() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut buffer = [0; 128];
+        let (mut sockets, mut server) = server(&mut storage, &mut buffer);
+        server.poll(&mut sockets, Instant::ZERO);
+
+        let bus_number = BusNumber::try_from(3).unwrap();
+        server.set_bus_number(&mut sockets, bus_number);
+        server.set_data_rate(Bitrate::Kbps1000);
+        server.set_mac_addr(EthernetAddress([0x02, 0, 0, 0, 0, 2]));
+
+        assert_eq!(server.bus_number(), bus_number);
+        assert_eq!(server.data_rate(), Bitrate::Kbps1000);
+        assert_eq!(server.mac_addr(), EthernetAddress([0x02, 0, 0, 0, 0, 2]));
+
+        // no header has been sent, so the listener is left alone
+        assert!(sockets.get::<Socket>(server.handle).is_listening());
+    }
+
+    #[test]
+    fn stats() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut buffer = [0; 128];
+        let (mut sockets, mut server) = server(&mut storage, &mut buffer);
+        server.poll(&mut sockets, Instant::ZERO);
+
+        // nothing can be sent without a client
+        let frame = Frame::new_zeroed();
+        assert!(server.send_frame(&mut sockets, &frame).is_err());
+        assert!(server.send_frames(&mut sockets, &[frame]).is_err());
+        assert!(server.send_heartbeat(&mut sockets).is_err());
+        assert_eq!(server.stats().send_errors, 3);
+        assert_eq!(server.stats().frames_tx, 0);
+
+        server.reset_stats();
+        assert_eq!(server.stats(), &Stats::default());
+    }
+
+    #[test]
+    fn poll_at() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut buffer = [0; 128];
+        let (mut sockets, mut server) = server(&mut storage, &mut buffer);
+
+        // the socket starts listening on the first poll
+        assert_eq!(
+            server.poll_at(&sockets, Instant::ZERO),
+            Some(Instant::ZERO)
+        );
+
+        // no heartbeats are due without a client
+        server.poll(&mut sockets, Instant::ZERO);
+        assert_eq!(server.poll_at(&sockets, Instant::ZERO), None);
+    }
+}
@@ -2,7 +2,8 @@
 
 use core::mem::size_of;
 
-use crate::BROADCAST;
+use crate::heartbeat::{self, Timer};
+use crate::{EchoQueue, Filters, Received, Stats, TxQueue, BROADCAST};
 use embedded_can::Frame as CanFrame;
 use smoltcp::{
     iface::{SocketHandle, SocketSet},
@@ -12,10 +13,12 @@ use smoltcp::{
     wire::{EthernetAddress, IpEndpoint},
 };
 use tritiumcan::{
-    datagram::{Frame, Header, Packet},
-    BusNumber, HEARTBEAT_INTERVAL, PORT, PROTOCOL_VERSION,
+    datagram::{
+        Conflict, DecodeError, Frame, Header, Heartbeat, Message, Packet,
+    },
+    Bitrate, BusNumber, PORT, PROTOCOL_VERSION,
 };
-use zerocopy::{AsBytes, FromZeroes};
+use zerocopy::{AsBytes, FromZeroes, Ref};
 
 /// Server instance.
 #[derive(Debug)]
@@ -26,10 +29,21 @@ pub struct Server {
     meta: UdpMetadata,
     mac_addr: [u8; 6],
     bus_number: BusNumber,
-    data_rate: u16,
+    data_rate: Bitrate,
+    heartbeat: heartbeat::Config,
+    rx_filters: Filters,
+    tx_filters: Filters,
+    tx_queue_enabled: bool,
+    echo_enabled: bool,
+    reflect_enabled: bool,
 
     // state
-    last_heartbeat: Instant,
+    heartbeat_timer: Timer,
+    last_poll: Instant,
+    conflict: Option<Conflict>,
+    tx_queue: TxQueue,
+    echo: EchoQueue,
+    stats: Stats,
 }
 
 impl Server {
@@ -41,7 +55,7 @@ impl Server {
         mac_addr: EthernetAddress,
         now: Instant,
         bus_number: BusNumber,
-        data_rate: u16,
+        data_rate: Bitrate,
     ) -> Server {
         let socket = Socket::new(rx_buffer, tx_buffer);
         let handle = sockets.add(socket);
@@ -60,7 +74,18 @@ impl Server {
             mac_addr: mac_addr.0,
             bus_number,
             data_rate,
-            last_heartbeat: now,
+            heartbeat: heartbeat::Config::new(),
+            rx_filters: Filters::new(),
+            tx_filters: Filters::new(),
+            tx_queue_enabled: false,
+            echo_enabled: false,
+            reflect_enabled: false,
+            heartbeat_timer: Timer::new(now, &mac_addr.0),
+            last_poll: now,
+            conflict: None,
+            tx_queue: TxQueue::new(),
+            echo: EchoQueue::new(),
+            stats: Stats::default(),
         }
     }
 
@@ -74,11 +99,137 @@ impl Server {
         self.bus_number = bus_number;
     }
 
+    /// Get the bitrate announced in heartbeats.
+    pub fn data_rate(&self) -> Bitrate {
+        self.data_rate
+    }
+
+    /// Set the bitrate announced in heartbeats.
+    pub fn set_data_rate(&mut self, data_rate: Bitrate) {
+        self.data_rate = data_rate;
+    }
+
+    /// Get the MAC address announced in heartbeats.
+    pub fn mac_addr(&self) -> EthernetAddress {
+        EthernetAddress(self.mac_addr)
+    }
+
+    /// Set the MAC address announced in heartbeats.
+    pub fn set_mac_addr(&mut self, mac_addr: EthernetAddress) {
+        self.mac_addr = mac_addr.0;
+    }
+
+    /// Get the filters applied to frames received from the network.
+    pub fn rx_filters(&self) -> &Filters {
+        &self.rx_filters
+    }
+
+    /// Set the filters applied to frames received from the network, frames
+    /// that do not match are dropped by [`Server::recv_frame`].
+    pub fn set_rx_filters(&mut self, filters: Filters) {
+        self.rx_filters = filters;
+    }
+
+    /// Get the filters applied to frames sent to the network.
+    pub fn tx_filters(&self) -> &Filters {
+        &self.tx_filters
+    }
+
+    /// Set the filters applied to frames sent to the network, frames that do
+    /// not match are silently dropped by [`Server::send_frame`].
+    pub fn set_tx_filters(&mut self, filters: Filters) {
+        self.tx_filters = filters;
+    }
+
+    /// Whether frames are queued when the socket cannot take them.
+    pub fn is_tx_queue_enabled(&self) -> bool {
+        self.tx_queue_enabled
+    }
+
+    /// Enable or disable the transmit queue.
+    ///
+    /// When enabled, [`Server::send_frame`] holds up to
+    /// [`TX_QUEUE_LEN`](crate::TX_QUEUE_LEN) frames and [`Server::poll`] sends
+    /// them lowest CAN ID first, as the bus would arbitrate them. Frames
+    /// already queued are still sent after the queue is disabled.
+    pub fn set_tx_queue_enabled(&mut self, enabled: bool) {
+        self.tx_queue_enabled = enabled;
+    }
+
+    /// Number of frames waiting in the transmit queue.
+    pub fn tx_queue_len(&self) -> usize {
+        self.tx_queue.len()
+    }
+
+    /// Whether sent frames are echoed back to the receive methods.
+    pub fn is_echo_enabled(&self) -> bool {
+        self.echo_enabled
+    }
+
+    /// Enable or disable local echo.
+    ///
+    /// When enabled, each frame written to the socket is also returned by
+    /// the receive methods with [`Received::echoed`] set, holding up to
+    /// [`ECHO_QUEUE_LEN`](crate::ECHO_QUEUE_LEN) echoes. Disabling drops any
+    /// echoes not yet received.
+    pub fn set_echo_enabled(&mut self, enabled: bool) {
+        self.echo_enabled = enabled;
+
+        if !enabled {
+            self.echo.clear();
+        }
+    }
+
+    /// Whether received frames are sent back to the network.
+    pub fn is_reflect_enabled(&self) -> bool {
+        self.reflect_enabled
+    }
+
+    /// Enable or disable reflecting received frames back to the network
+    /// for link testing.
+    ///
+    /// Frames are reflected as they are received, bypassing the transmit
+    /// filters and queue.
+    pub fn set_reflect_enabled(&mut self, enabled: bool) {
+        self.reflect_enabled = enabled;
+    }
+
+    /// Traffic counters since the server was created or last reset.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Reset all traffic counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Get the heartbeat configuration.
+    pub fn heartbeat_config(&self) -> &heartbeat::Config {
+        &self.heartbeat
+    }
+
+    /// Set a new heartbeat configuration, taking effect from the current
+    /// interval.
+    pub fn set_heartbeat_config(&mut self, config: heartbeat::Config) {
+        self.heartbeat = config;
+    }
+
+    /// Take the most recent conflict with another adapter on the network.
+    ///
+    /// Conflicts are detected from heartbeats passing through
+    /// [`Server::recv_frame`].
+    pub fn take_conflict(&mut self) -> Option<Conflict> {
+        self.conflict.take()
+    }
+
     /// Perform bufferred transactions and send heartbeat if needed.
     ///
-    /// This function should be called at least every 10ms to keep up with traffic.
+    /// Call after each interface poll and again by the time returned from
+    /// [`Server::poll_at`].
     pub fn poll(&mut self, sockets: &mut SocketSet, now: Instant) {
         let socket = sockets.get_mut::<Socket>(self.handle);
+        self.last_poll = now;
 
         if !socket.is_open() {
             match socket.bind(PORT) {
@@ -90,18 +241,51 @@ impl Server {
             }
         }
 
-        if now - self.last_heartbeat > HEARTBEAT_INTERVAL.into() {
+        if self.heartbeat_timer.is_due(&self.heartbeat, now) {
             match self.write_heartbeat(socket) {
-                Ok(_) => self.last_heartbeat = now,
+                Ok(_) => self.heartbeat_timer.reset(&self.heartbeat, now),
                 Err(_err) => {
                     #[cfg(feature = "defmt-03")]
                     defmt::error!("Failed to send heartbeat: {}", _err);
                 }
             }
         }
+
+        self.drain_tx_queue(socket);
     }
 
-    /// Broadcast heartbeat.
+    /// Time [`Server::poll`] next needs to be called, `None` if it only needs
+    /// calling after the interface received packets.
+    ///
+    /// Returns `now` if there is work pending, so that a device can sleep
+    /// until the earlier of this and [`Interface::poll_at`].
+    ///
+    /// [`Interface::poll_at`]: smoltcp::iface::Interface::poll_at
+    pub fn poll_at(
+        &self,
+        sockets: &SocketSet,
+        now: Instant,
+    ) -> Option<Instant> {
+        let socket = sockets.get::<Socket>(self.handle);
+
+        if !socket.is_open() || !self.tx_queue.is_empty() && socket.can_send() {
+            return Some(now);
+        }
+
+        self.heartbeat_timer.next(&self.heartbeat)
+    }
+
+    fn drain_tx_queue(&mut self, socket: &mut Socket) {
+        while let Some(&frame) = self.tx_queue.peek() {
+            if self.write_frame(socket, &frame).is_err() {
+                break;
+            }
+
+            self.tx_queue.pop();
+        }
+    }
+
+    /// Broadcast heartbeat to the configured destinations.
     ///
     /// Note: this doesn't reset the heartbeat interval.
     pub fn send_heartbeat(
@@ -113,27 +297,142 @@ impl Server {
         self.write_heartbeat(socket)
     }
 
-    fn write_heartbeat(&self, socket: &mut Socket) -> Result<(), SendError> {
+    fn write_heartbeat(
+        &mut self,
+        socket: &mut Socket,
+    ) -> Result<(), SendError> {
         let packet = Packet::new_heartbeat(
             &self.mac_addr,
             &self.bus_number,
             &self.data_rate,
         );
 
-        socket.send_slice(packet.as_bytes(), self.meta)
+        let multicast = self.heartbeat.multicast().then_some(self.meta);
+        let destinations = self.heartbeat.destinations().map(Into::into);
+        let mut result = Ok(());
+
+        for meta in multicast.into_iter().chain(destinations) {
+            match socket.send_slice(packet.as_bytes(), meta) {
+                Ok(_) => {
+                    self.stats.heartbeats_tx += 1;
+                    self.stats.bytes_tx += size_of::<Packet>() as u64;
+                }
+                Err(err) => {
+                    self.stats.send_errors += 1;
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
     }
 
     /// Broadcast a CAN frame.
+    ///
+    /// With the transmit queue enabled the frame is queued and sent in
+    /// priority order, failing with [`SendError::BufferFull`] only if the
+    /// queue is full.
+    ///
+    /// Frames with more than 8 data bytes are dropped and counted as send
+    /// errors, as in [`Server::send_frames`].
     pub fn send_frame(
         &mut self,
         sockets: &mut SocketSet,
         frame: &impl CanFrame,
     ) -> Result<(), SendError> {
+        if !self.tx_filters.matches(frame) {
+            return Ok(());
+        }
+
         let socket = sockets.get_mut::<Socket>(self.handle);
+        let Some(frame) = Frame::from_frame(frame) else {
+            self.stats.send_errors += 1;
+            return Ok(());
+        };
+
+        let result = if self.tx_queue_enabled {
+            self.enqueue(socket, frame)
+        } else {
+            self.write_frame(socket, &frame)
+        };
+
+        if result.is_err() {
+            self.stats.send_errors += 1;
+        }
+
+        result
+    }
+
+    /// Broadcast several CAN frames.
+    ///
+    /// Returns the number of frames taken from the start of `frames`, frames
+    /// rejected by the transmit filters count as taken. Fewer than
+    /// `frames.len()` are taken once the socket, or the transmit queue when
+    /// enabled, is full. Frames with more than 8 data bytes are skipped and
+    /// counted as send errors.
+    pub fn send_frames<F: CanFrame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        frames: &[F],
+    ) -> Result<usize, SendError> {
+        let socket = sockets.get_mut::<Socket>(self.handle);
+        let mut taken = 0;
+
+        for frame in frames {
+            if self.tx_filters.matches(frame) {
+                let Some(frame) = Frame::from_frame(frame) else {
+                    self.stats.send_errors += 1;
+                    taken += 1;
+                    continue;
+                };
+
+                let result = if self.tx_queue_enabled {
+                    self.enqueue(socket, frame)
+                } else {
+                    self.write_frame(socket, &frame)
+                };
 
+                if let Err(err) = result {
+                    self.stats.send_errors += 1;
+
+                    match err {
+                        SendError::BufferFull => break,
+                        err => return Err(err),
+                    }
+                }
+            }
+            taken += 1;
+        }
+
+        self.drain_tx_queue(socket);
+
+        Ok(taken)
+    }
+
+    /// Queue a frame, making room by sending queued frames if needed.
+    fn enqueue(
+        &mut self,
+        socket: &mut Socket,
+        frame: Frame,
+    ) -> Result<(), SendError> {
+        if self.tx_queue.push(frame).is_err() {
+            self.drain_tx_queue(socket);
+            self.tx_queue
+                .push(frame)
+                .map_err(|_| SendError::BufferFull)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_frame(
+        &mut self,
+        socket: &mut Socket,
+        frame: &Frame,
+    ) -> Result<(), SendError> {
         let mut packet = Packet {
             header: Header::new(),
-            frame: Frame::from_frame(frame).unwrap(),
+            frame: *frame,
         };
         packet.header.set_version(PROTOCOL_VERSION);
         packet.header.set_bus_number(self.bus_number.into());
@@ -141,22 +440,180 @@ impl Server {
             .header
             .set_client_identifier(u64::from_be_bytes([0u8; 8]));
 
-        socket.send_slice(packet.as_bytes(), self.meta)
+        socket.send_slice(packet.as_bytes(), self.meta)?;
+        self.stats.frames_tx += 1;
+        self.stats.bytes_tx += size_of::<Packet>() as u64;
+
+        if self.echo_enabled {
+            self.echo.push(self.last_poll, *frame);
+        }
+
+        Ok(())
+    }
+
+    /// Send a received frame back to the network if reflection is enabled.
+    fn reflect(&mut self, socket: &mut Socket, frame: &Frame) {
+        if self.reflect_enabled && self.write_frame(socket, frame).is_err() {
+            self.stats.send_errors += 1;
+        }
     }
 
+    /// Receive a CAN frame.
+    ///
+    /// Heartbeats from other adapters are consumed and checked for conflicts,
+    /// frames rejected by the receive filters are dropped. Pending local
+    /// echoes are returned before frames from the network.
     pub fn recv_frame(
         &mut self,
         sockets: &mut SocketSet,
-    ) -> Result<Option<Frame>, RecvError> {
+    ) -> Result<Option<Received>, RecvError> {
+        if let Some(echo) = self.echo.pop() {
+            return Ok(Some(echo));
+        }
+
         let socket = sockets.get_mut::<Socket>(self.handle);
 
+        Ok(self.read_frame(socket)?.map(|frame| Received {
+            timestamp: self.last_poll,
+            frame,
+            echoed: false,
+        }))
+    }
+
+    /// Receive all available CAN frames, up to `frames.len()`.
+    ///
+    /// Returns the number of frames written to the start of `frames`, see
+    /// [`Server::recv_frame`] for which datagrams are skipped.
+    pub fn recv_frames(
+        &mut self,
+        sockets: &mut SocketSet,
+        frames: &mut [Received],
+    ) -> usize {
+        let socket = sockets.get_mut::<Socket>(self.handle);
+        let mut count = 0;
+
+        while count < frames.len() {
+            let Some(echo) = self.echo.pop() else {
+                break;
+            };
+            frames[count] = echo;
+            count += 1;
+        }
+
+        while count < frames.len() {
+            match self.read_frame(socket) {
+                Ok(Some(frame)) => {
+                    frames[count] = Received {
+                        timestamp: self.last_poll,
+                        frame,
+                        echoed: false,
+                    };
+                    count += 1;
+                }
+                Ok(None) | Err(RecvError::Truncated) => {}
+                Err(RecvError::Exhausted) => break,
+            }
+        }
+
+        count
+    }
+
+    fn read_frame(
+        &mut self,
+        socket: &mut Socket,
+    ) -> Result<Option<Frame>, RecvError> {
         let mut packet = Packet::new_zeroed();
         let (len, _meta) = socket.recv_slice(packet.as_bytes_mut())?;
+        self.stats.bytes_rx += len as u64;
 
         if len != size_of::<Packet>() {
-            Ok(None)
-        } else {
-            Ok(Some(packet.frame.into()))
+            self.stats.malformed += 1;
+            return Ok(None);
+        }
+
+        if !self.accept(&packet) {
+            return Ok(None);
+        }
+
+        self.reflect(socket, &packet.frame);
+
+        Ok(Some(packet.frame))
+    }
+
+    /// Receive a CAN frame without copying it out of the socket buffer.
+    ///
+    /// `f` is called with a view of the received packet and its result is
+    /// returned. Datagrams are skipped in the same way as
+    /// [`Server::recv_frame`], returning `None` without calling `f`. Local
+    /// echoes are only returned by the copying receive methods.
+    pub fn recv_with<R>(
+        &mut self,
+        sockets: &mut SocketSet,
+        f: impl FnOnce(Ref<&[u8], Packet>) -> R,
+    ) -> Result<Option<R>, RecvError> {
+        let socket = sockets.get_mut::<Socket>(self.handle);
+
+        let (buf, _meta) = socket.recv()?;
+        self.stats.bytes_rx += buf.len() as u64;
+
+        let Some(packet) = Ref::<_, Packet>::new(buf) else {
+            self.stats.malformed += 1;
+            return Ok(None);
+        };
+
+        if !self.accept(&packet) {
+            return Ok(None);
+        }
+
+        let frame = packet.frame;
+        let result = f(packet);
+        self.reflect(socket, &frame);
+
+        Ok(Some(result))
+    }
+
+    /// Check heartbeats for conflicts and frames against the bus number and
+    /// receive filters, returning whether the packet holds a frame for the
+    /// caller.
+    fn accept(&mut self, packet: &Packet) -> bool {
+        match packet.message() {
+            Ok(Message::Heartbeat(heartbeat)) => {
+                let own = Heartbeat {
+                    bus_number: self.bus_number,
+                    data_rate: self.data_rate,
+                    mac_addr: self.mac_addr,
+                };
+
+                if let Some(conflict) = own.conflict(&heartbeat) {
+                    #[cfg(feature = "defmt-03")]
+                    defmt::warn!("Conflicting adapter: {}", conflict);
+                    self.conflict = Some(conflict);
+                }
+
+                false
+            }
+            Ok(Message::Settings(_)) => false,
+            Ok(Message::Frame(frame)) => {
+                if packet.header.bus_number() != u8::from(self.bus_number) {
+                    self.stats.wrong_bus += 1;
+                    return false;
+                }
+
+                if !self.rx_filters.matches(&frame) {
+                    return false;
+                }
+
+                self.stats.frames_rx += 1;
+                true
+            }
+            Err(DecodeError::Version) => {
+                self.stats.wrong_version += 1;
+                false
+            }
+            Err(_) => {
+                self.stats.malformed += 1;
+                false
+            }
         }
     }
 
@@ -190,3 +647,248 @@ impl Server {
         socket.register_send_waker(waker);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::StandardId;
+    use smoltcp::{
+        iface::SocketStorage, socket::udp::PacketMetadata, time::Duration,
+    };
+    use tritiumcan::filter::Rule;
+
+    /// Create a server with single packet socket buffers, so that one queued
+    /// datagram fills the transmit buffer.
+    ///
+    /// With `heartbeats` false, heartbeats are disabled and the socket is
+    /// bound by polling once at time zero.
+    fn server<'a>(
+        storage: &'a mut [SocketStorage<'a>; 1],
+        meta: &'a mut [PacketMetadata; 2],
+        payload: &'a mut [u8; 128],
+        heartbeats: bool,
+    ) -> (SocketSet<'a>, Server) {
+        let mut sockets = SocketSet::new(&mut storage[..]);
+        let (rx_meta, tx_meta) = meta.split_at_mut(1);
+        let (rx_payload, tx_payload) = payload.split_at_mut(64);
+
+        let mut server = Server::new(
+            &mut sockets,
+            PacketBuffer::new(rx_meta, rx_payload),
+            PacketBuffer::new(tx_meta, tx_payload),
+            EthernetAddress([0x02, 0, 0, 0, 0, 1]),
+            Instant::from_millis(0),
+            BusNumber::default(),
+            Bitrate::Kbps500,
+        );
+
+        if !heartbeats {
+            let mut config = heartbeat::Config::new();
+            config.set_enabled(false);
+            server.set_heartbeat_config(config);
+            server.poll(&mut sockets, Instant::from_millis(0));
+        }
+
+        (sockets, server)
+    }
+
+    #[test]
+    fn heartbeat_config() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut meta = [PacketMetadata::EMPTY; 2];
+        let mut payload = [0; 128];
+        let (mut sockets, mut server) =
+            server(&mut storage, &mut meta, &mut payload, false);
+
+        // the single packet tx buffer fills once a heartbeat is queued
+        server.poll(&mut sockets, Instant::from_secs(10));
+        assert!(sockets.get::<Socket>(server.handle).can_send());
+
+        let mut config = heartbeat::Config::new();
+        config.set_interval(Duration::from_millis(500));
+        server.set_heartbeat_config(config);
+
+        server.poll(&mut sockets, Instant::from_millis(499));
+        assert!(sockets.get::<Socket>(server.handle).can_send());
+
+        server.poll(&mut sockets, Instant::from_millis(500));
+        assert!(!sockets.get::<Socket>(server.handle).can_send());
+    }
+
+    #[test]
+    fn tx_filters() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut meta = [PacketMetadata::EMPTY; 2];
+        let mut payload = [0; 128];
+        let (mut sockets, mut server) =
+            server(&mut storage, &mut meta, &mut payload, false);
+
+        let id = StandardId::new(0x100).unwrap();
+        let mut filters = Filters::new();
+        filters.add(Rule::standard_mask(id, 0x7FF));
+        server.set_tx_filters(filters);
+
+        let rejected = StandardId::new(0x101).unwrap();
+        let frame = <Frame as CanFrame>::new(rejected, &[1]).unwrap();
+        server.send_frame(&mut sockets, &frame).unwrap();
+        assert!(sockets.get::<Socket>(server.handle).can_send());
+
+        let frame = <Frame as CanFrame>::new(id, &[1]).unwrap();
+        server.send_frame(&mut sockets, &frame).unwrap();
+        assert!(!sockets.get::<Socket>(server.handle).can_send());
+    }
+
+    #[test]
+    fn tx_queue() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut meta = [PacketMetadata::EMPTY; 2];
+        let mut payload = [0; 128];
+        let (mut sockets, mut server) =
+            server(&mut storage, &mut meta, &mut payload, false);
+        server.set_tx_queue_enabled(true);
+
+        let id = StandardId::new(0x100).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[1]).unwrap();
+
+        // the first frame fills the socket buffer, the rest wait in the queue
+        for _ in 0..=crate::TX_QUEUE_LEN {
+            server.send_frame(&mut sockets, &frame).unwrap();
+        }
+        assert_eq!(server.tx_queue_len(), crate::TX_QUEUE_LEN);
+        assert_eq!(
+            server.send_frame(&mut sockets, &frame),
+            Err(SendError::BufferFull)
+        );
+    }
+
+    /// Frame with a data length code the protocol cannot carry.
+    struct LongFrame;
+
+    impl CanFrame for LongFrame {
+        fn new(_: impl Into<embedded_can::Id>, _: &[u8]) -> Option<Self> {
+            None
+        }
+
+        fn new_remote(
+            _: impl Into<embedded_can::Id>,
+            _: usize,
+        ) -> Option<Self> {
+            None
+        }
+
+        fn is_extended(&self) -> bool {
+            false
+        }
+
+        fn is_remote_frame(&self) -> bool {
+            true
+        }
+
+        fn id(&self) -> embedded_can::Id {
+            StandardId::ZERO.into()
+        }
+
+        fn dlc(&self) -> usize {
+            9
+        }
+
+        fn data(&self) -> &[u8] {
+            &[]
+        }
+    }
+
+    #[test]
+    fn send_invalid() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut meta = [PacketMetadata::EMPTY; 2];
+        let mut payload = [0; 128];
+        let (mut sockets, mut server) =
+            server(&mut storage, &mut meta, &mut payload, false);
+
+        assert_eq!(server.send_frames(&mut sockets, &[LongFrame]), Ok(1));
+        assert_eq!(server.send_frame(&mut sockets, &LongFrame), Ok(()));
+        assert!(sockets.get::<Socket>(server.handle).can_send());
+        assert_eq!(server.stats().send_errors, 2);
+    }
+
+    #[test]
+    fn stats() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut meta = [PacketMetadata::EMPTY; 2];
+        let mut payload = [0; 128];
+        let (mut sockets, mut server) =
+            server(&mut storage, &mut meta, &mut payload, false);
+
+        let id = StandardId::new(0x100).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[1]).unwrap();
+        server.send_frame(&mut sockets, &frame).unwrap();
+        assert!(server.send_frame(&mut sockets, &frame).is_err());
+        assert!(server.send_heartbeat(&mut sockets).is_err());
+
+        assert_eq!(
+            server.stats(),
+            &Stats {
+                frames_tx: 1,
+                bytes_tx: size_of::<Packet>() as u64,
+                send_errors: 2,
+                ..Stats::default()
+            }
+        );
+
+        server.reset_stats();
+        assert_eq!(server.stats(), &Stats::default());
+    }
+
+    #[test]
+    fn poll_at() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut meta = [PacketMetadata::EMPTY; 2];
+        let mut payload = [0; 128];
+        let (mut sockets, mut server) =
+            server(&mut storage, &mut meta, &mut payload, true);
+
+        // the socket is bound on the first poll
+        let now = Instant::from_millis(10);
+        assert_eq!(server.poll_at(&sockets, now), Some(now));
+
+        server.poll(&mut sockets, now);
+        assert_eq!(
+            server.poll_at(&sockets, now),
+            Some(Instant::from_millis(1000))
+        );
+
+        let mut config = heartbeat::Config::new();
+        config.set_enabled(false);
+        server.set_heartbeat_config(config);
+        assert_eq!(server.poll_at(&sockets, now), None);
+    }
+
+    #[test]
+    fn echo() {
+        let mut storage = [SocketStorage::EMPTY; 1];
+        let mut meta = [PacketMetadata::EMPTY; 2];
+        let mut payload = [0; 128];
+        let (mut sockets, mut server) =
+            server(&mut storage, &mut meta, &mut payload, false);
+        server.set_echo_enabled(true);
+        server.poll(&mut sockets, Instant::from_millis(5));
+
+        let id = StandardId::new(0x100).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[1]).unwrap();
+        server.send_frame(&mut sockets, &frame).unwrap();
+
+        // frames that could not be sent are not echoed
+        assert!(server.send_frame(&mut sockets, &frame).is_err());
+
+        assert_eq!(
+            server.recv_frame(&mut sockets),
+            Ok(Some(Received {
+                timestamp: Instant::from_millis(5),
+                frame,
+                echoed: true,
+            }))
+        );
+        assert_eq!(server.recv_frame(&mut sockets), Err(RecvError::Exhausted));
+    }
+}
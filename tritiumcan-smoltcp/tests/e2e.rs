@@ -0,0 +1,733 @@
+//! End-to-end tests running the servers against a client interface over an
+//! in-memory Ethernet link, with simulated time.
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use embedded_can::StandardId;
+use smoltcp::{
+    iface::{Config, Interface, SocketHandle, SocketSet},
+    phy::{self, Device, DeviceCapabilities, Medium},
+    socket::{tcp, udp},
+    time::{Duration, Instant},
+    wire::{EthernetAddress, IpAddress, IpCidr, IpEndpoint},
+};
+use tritiumcan_smoltcp::{
+    proto::{
+        datagram::{Frame, Header, Heartbeat, Message, Packet, FRAME_LEN},
+        stream::HEADER_LEN,
+        Bitrate, BusNumber, BROADCAST, PORT,
+    },
+    tcp::{Event, Server as TcpServer},
+    udp::Server as UdpServer,
+    Received,
+};
+use zerocopy::{AsBytes, FromBytes};
+
+type Wire = Rc<RefCell<VecDeque<Vec<u8>>>>;
+
+/// One end of an in-memory Ethernet link.
+struct Port {
+    rx: Wire,
+    tx: Wire,
+}
+
+/// Connect two ports, every frame sent by one is received by the other.
+fn link() -> (Port, Port) {
+    let (a, b) = (Wire::default(), Wire::default());
+
+    (
+        Port {
+            rx: a.clone(),
+            tx: b.clone(),
+        },
+        Port { rx: b, tx: a },
+    )
+}
+
+impl Device for Port {
+    type RxToken<'a> = RxToken;
+    type TxToken<'a> = TxToken;
+
+    fn receive(
+        &mut self,
+        _timestamp: Instant,
+    ) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let buffer = self.rx.borrow_mut().pop_front()?;
+        Some((RxToken(buffer), TxToken(self.tx.clone())))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(TxToken(self.tx.clone()))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = 1514;
+        caps.medium = Medium::Ethernet;
+        caps
+    }
+}
+
+struct RxToken(Vec<u8>);
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.0)
+    }
+}
+
+struct TxToken(Wire);
+
+impl phy::TxToken for TxToken {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buffer = vec![0; len];
+        let result = f(&mut buffer);
+        self.0.borrow_mut().push_back(buffer);
+        result
+    }
+}
+
+/// An interface on the link, with its own sockets.
+struct Host {
+    device: Port,
+    iface: Interface,
+    sockets: SocketSet<'static>,
+}
+
+impl Host {
+    /// Host `n` has MAC address `02:00:00:00:00:n` and IP address
+    /// `10.0.0.n/24`, and joins the adapter multicast group.
+    fn new(mut device: Port, n: u8) -> Self {
+        let mut iface =
+            Interface::new(Config::new(mac_addr(n).into()), &mut device, t(0));
+        iface.update_ip_addrs(|addrs| {
+            addrs.push(IpCidr::new(ip_addr(n), 24)).unwrap();
+        });
+        iface
+            .join_multicast_group(&mut device, group(), t(0))
+            .unwrap();
+
+        Self {
+            device,
+            iface,
+            sockets: SocketSet::new(vec![]),
+        }
+    }
+
+    fn poll(&mut self, now: Instant) -> bool {
+        self.iface.poll(now, &mut self.device, &mut self.sockets)
+    }
+}
+
+fn t(millis: u64) -> Instant {
+    Instant::from_millis(millis as i64)
+}
+
+fn mac_addr(n: u8) -> EthernetAddress {
+    EthernetAddress([0x02, 0, 0, 0, 0, n])
+}
+
+fn ip_addr(n: u8) -> IpAddress {
+    IpAddress::v4(10, 0, 0, n)
+}
+
+fn group() -> IpAddress {
+    let std::net::IpAddr::V4(addr) = BROADCAST else {
+        unreachable!()
+    };
+    let [a, b, c, d] = addr.octets();
+    IpAddress::v4(a, b, c, d)
+}
+
+fn bus(n: u8) -> BusNumber {
+    BusNumber::try_from(n).unwrap()
+}
+
+fn frame(id: u16, data: &[u8]) -> Frame {
+    let id = StandardId::new(id).unwrap();
+    <Frame as embedded_can::Frame>::new(id, data).unwrap()
+}
+
+const ADAPTER: u8 = 1;
+const CLIENT: u8 = 2;
+
+/// UDP server on the adapter, with a client socket bound to the protocol
+/// port.
+struct UdpNet {
+    now: Instant,
+    adapter: Host,
+    server: UdpServer,
+    client: Host,
+    socket: SocketHandle,
+}
+
+impl UdpNet {
+    fn new() -> Self {
+        let (a, b) = link();
+        let mut adapter = Host::new(a, ADAPTER);
+        let mut client = Host::new(b, CLIENT);
+
+        let server = UdpServer::new(
+            &mut adapter.sockets,
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; 16],
+                vec![0; 1024],
+            ),
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; 16],
+                vec![0; 1024],
+            ),
+            mac_addr(ADAPTER),
+            t(0),
+            BusNumber::default(),
+            Bitrate::Kbps500,
+        );
+
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; 16],
+                vec![0; 1024],
+            ),
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; 16],
+                vec![0; 1024],
+            ),
+        );
+        socket.bind(PORT).unwrap();
+        let socket = client.sockets.add(socket);
+
+        Self {
+            now: t(0),
+            adapter,
+            server,
+            client,
+            socket,
+        }
+    }
+
+    /// Advance time in 1 ms steps, delivering all traffic at each step.
+    fn run(&mut self, millis: u64) {
+        for _ in 0..millis {
+            self.now += Duration::from_millis(1);
+
+            loop {
+                let adapter = self.adapter.poll(self.now);
+                self.server.poll(&mut self.adapter.sockets, self.now);
+                let client = self.client.poll(self.now);
+
+                if !adapter && !client {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Send a packet from the client to the multicast group.
+    fn send(&mut self, packet: &Packet) {
+        let socket = self.client.sockets.get_mut::<udp::Socket>(self.socket);
+        socket
+            .send_slice(packet.as_bytes(), (group(), PORT))
+            .unwrap();
+    }
+
+    /// Messages received by the client.
+    fn recv(&mut self) -> Vec<Message> {
+        let socket = self.client.sockets.get_mut::<udp::Socket>(self.socket);
+        let mut messages = vec![];
+
+        while let Ok((buf, _meta)) = socket.recv() {
+            messages.push(Message::decode(buf).unwrap());
+        }
+
+        messages
+    }
+
+    /// Frames received by the client, ignoring heartbeats.
+    fn recv_frames(&mut self) -> Vec<Frame> {
+        self.recv()
+            .into_iter()
+            .filter_map(|message| match message {
+                Message::Frame(frame) => Some(frame),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn udp_frames() {
+    let mut net = UdpNet::new();
+    net.run(1);
+
+    net.send(
+        &Packet::new_frame(&BusNumber::default(), &frame(0x10, &[1])).unwrap(),
+    );
+    net.run(1);
+
+    assert_eq!(
+        net.server.recv_frame(&mut net.adapter.sockets),
+        Ok(Some(Received {
+            timestamp: net.now,
+            frame: frame(0x10, &[1]),
+            echoed: false,
+        }))
+    );
+
+    net.server
+        .send_frame(&mut net.adapter.sockets, &frame(0x20, &[2, 3]))
+        .unwrap();
+    net.run(1);
+
+    assert_eq!(net.recv_frames(), [frame(0x20, &[2, 3])]);
+    assert_eq!(net.server.stats().frames_rx, 1);
+    assert_eq!(net.server.stats().frames_tx, 1);
+}
+
+#[test]
+fn udp_batch() {
+    let mut net = UdpNet::new();
+    net.run(1);
+
+    for n in 0..4 {
+        net.send(
+            &Packet::new_frame(&BusNumber::default(), &frame(n, &[])).unwrap(),
+        );
+    }
+    net.run(1);
+
+    let mut received = [Received::default(); 8];
+    let count = net
+        .server
+        .recv_frames(&mut net.adapter.sockets, &mut received);
+    assert_eq!(count, 4);
+    for (n, received) in received[..count].iter().enumerate() {
+        assert_eq!(received.frame, frame(n as u16, &[]));
+    }
+
+    let frames: Vec<_> = (4..8).map(|n| frame(n, &[])).collect();
+    let sent = net
+        .server
+        .send_frames(&mut net.adapter.sockets, &frames)
+        .unwrap();
+    assert_eq!(sent, 4);
+    net.run(1);
+
+    assert_eq!(net.recv_frames(), frames);
+}
+
+#[test]
+fn udp_recv_with() {
+    let mut net = UdpNet::new();
+    net.run(1);
+
+    net.send(
+        &Packet::new_frame(&BusNumber::default(), &frame(0x10, &[1])).unwrap(),
+    );
+    net.run(1);
+
+    let id = net
+        .server
+        .recv_with(&mut net.adapter.sockets, |packet| packet.frame.id());
+    assert_eq!(id, Ok(Some(0x10)));
+}
+
+#[test]
+fn udp_wrong_bus() {
+    let mut net = UdpNet::new();
+    net.run(1);
+
+    net.send(&Packet::new_frame(&bus(2), &frame(0x10, &[1])).unwrap());
+    net.run(1);
+
+    assert_eq!(net.server.recv_frame(&mut net.adapter.sockets), Ok(None));
+    assert_eq!(net.server.stats().wrong_bus, 1);
+}
+
+#[test]
+fn udp_heartbeats() {
+    let mut net = UdpNet::new();
+    net.run(3500);
+
+    let heartbeats: Vec<_> = net
+        .recv()
+        .into_iter()
+        .filter_map(|message| match message {
+            Message::Heartbeat(heartbeat) => Some(heartbeat),
+            _ => None,
+        })
+        .collect();
+
+    let own = Heartbeat {
+        bus_number: BusNumber::default(),
+        data_rate: Bitrate::Kbps500,
+        mac_addr: mac_addr(ADAPTER).0,
+    };
+    assert_eq!(heartbeats, [own; 3]);
+    assert_eq!(net.server.stats().heartbeats_tx, 3);
+}
+
+#[test]
+fn udp_conflict() {
+    let mut net = UdpNet::new();
+    net.run(1);
+
+    let other = mac_addr(CLIENT).0;
+    net.send(&Packet::new_heartbeat(
+        &other,
+        &BusNumber::default(),
+        &Bitrate::Kbps250,
+    ));
+    net.run(1);
+
+    // heartbeats are consumed while receiving frames
+    assert_eq!(net.server.recv_frame(&mut net.adapter.sockets), Ok(None));
+    assert!(net.server.take_conflict().is_some());
+    assert!(net.server.take_conflict().is_none());
+}
+
+#[test]
+fn udp_echo_and_reflect() {
+    let mut net = UdpNet::new();
+    net.server.set_echo_enabled(true);
+    net.server.set_reflect_enabled(true);
+    net.run(1);
+
+    net.send(
+        &Packet::new_frame(&BusNumber::default(), &frame(0x10, &[1])).unwrap(),
+    );
+    net.run(1);
+
+    let received = net
+        .server
+        .recv_frame(&mut net.adapter.sockets)
+        .unwrap()
+        .unwrap();
+    assert!(!received.echoed);
+    net.run(1);
+
+    // the reflected frame is sent back to the network and echoed locally
+    assert_eq!(net.recv_frames(), [frame(0x10, &[1])]);
+    let echo = net
+        .server
+        .recv_frame(&mut net.adapter.sockets)
+        .unwrap()
+        .unwrap();
+    assert!(echo.echoed);
+    assert_eq!(echo.frame, frame(0x10, &[1]));
+}
+
+/// TCP server on the adapter, with a client socket connecting to it.
+struct TcpNet {
+    now: Instant,
+    adapter: Host,
+    server: TcpServer,
+    client: Host,
+    socket: SocketHandle,
+    local_port: u16,
+    events: Vec<Event>,
+}
+
+impl TcpNet {
+    fn new() -> Self {
+        let (a, b) = link();
+        let mut adapter = Host::new(a, ADAPTER);
+        let mut client = Host::new(b, CLIENT);
+
+        let server = TcpServer::new(
+            &mut adapter.sockets,
+            tcp::SocketBuffer::new(vec![0; 1024]),
+            tcp::SocketBuffer::new(vec![0; 1024]),
+            mac_addr(ADAPTER),
+            t(0),
+            BusNumber::default(),
+            Bitrate::Kbps500,
+        );
+
+        let socket = client.sockets.add(tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; 1024]),
+            tcp::SocketBuffer::new(vec![0; 1024]),
+        ));
+
+        Self {
+            now: t(0),
+            adapter,
+            server,
+            client,
+            socket,
+            local_port: 49152,
+            events: vec![],
+        }
+    }
+
+    fn client(&mut self) -> &mut tcp::Socket<'static> {
+        self.client.sockets.get_mut::<tcp::Socket>(self.socket)
+    }
+
+    /// Advance time in 1 ms steps, delivering all traffic at each step and
+    /// collecting server events.
+    fn run(&mut self, millis: u64) {
+        for _ in 0..millis {
+            self.now += Duration::from_millis(1);
+
+            loop {
+                let adapter = self.adapter.poll(self.now);
+                let event =
+                    self.server.poll(&mut self.adapter.sockets, self.now);
+                let client = self.client.poll(self.now);
+
+                self.events.extend(event);
+
+                if !adapter && !client && event.is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Connect the client from a new local port.
+    fn connect(&mut self) {
+        self.local_port += 1;
+        let (port, cx) = (self.local_port, self.client.iface.context());
+        self.client
+            .sockets
+            .get_mut::<tcp::Socket>(self.socket)
+            .connect(cx, (ip_addr(ADAPTER), PORT), port)
+            .unwrap();
+        self.run(10);
+    }
+
+    /// Connect and send the client stream header, discarding the server
+    /// stream header.
+    fn handshake(&mut self) {
+        self.connect();
+        self.send(Packet::new_stream_header(&BusNumber::default()).as_bytes());
+        assert_eq!(self.recv().len(), HEADER_LEN);
+    }
+
+    fn send(&mut self, bytes: &[u8]) {
+        assert_eq!(self.client().send_slice(bytes), Ok(bytes.len()));
+        self.run(50);
+    }
+
+    /// Bytes received by the client.
+    fn recv(&mut self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // twice, in case the data wraps around the end of the ring buffer
+        let _ = self.client().recv(|buf| {
+            bytes.extend_from_slice(buf);
+            (buf.len(), ())
+        });
+        let _ = self.client().recv(|buf| {
+            bytes.extend_from_slice(buf);
+            (buf.len(), ())
+        });
+        bytes
+    }
+
+    fn events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[test]
+fn tcp_handshake() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.connect();
+
+    let remote = IpEndpoint::new(ip_addr(CLIENT), net.local_port);
+    assert_eq!(net.events(), [Event::ClientConnected { remote }]);
+    assert_eq!(net.server.remote(), Some(remote));
+
+    // the server sends its stream header once connected
+    let header = net.recv();
+    assert_eq!(header.len(), HEADER_LEN);
+    let header = Packet::read_from(&header[..]).unwrap().header;
+    assert_eq!(header, Header::with_bus_number(&BusNumber::default()));
+
+    // frames are only received after the client stream header
+    net.send(frame(0x10, &[1]).as_bytes());
+    assert_eq!(net.server.recv_frame(&mut net.adapter.sockets), Ok(None));
+}
+
+#[test]
+fn tcp_frames() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.handshake();
+
+    net.send(frame(0x10, &[1]).as_bytes());
+    let received = net.server.recv_frame(&mut net.adapter.sockets).unwrap();
+    assert_eq!(received.map(|r| r.frame), Some(frame(0x10, &[1])));
+
+    net.server
+        .send_frame(&mut net.adapter.sockets, &frame(0x20, &[2]))
+        .unwrap();
+    net.run(50);
+    assert_eq!(net.recv(), frame(0x20, &[2]).as_bytes());
+}
+
+#[test]
+fn tcp_batch() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.handshake();
+
+    let frames: Vec<_> = (0..8).map(|n| frame(n, &[n as u8])).collect();
+    let bytes: Vec<u8> =
+        frames.iter().flat_map(|f| f.as_bytes().to_vec()).collect();
+    net.send(&bytes);
+
+    let mut received = [Received::default(); 16];
+    let count = net
+        .server
+        .recv_frames(&mut net.adapter.sockets, &mut received)
+        .unwrap();
+    assert_eq!(count, frames.len());
+    for (received, frame) in received.iter().zip(&frames) {
+        assert_eq!(&received.frame, frame);
+        assert!(received.timestamp <= net.now);
+    }
+
+    let sent = net
+        .server
+        .send_frames(&mut net.adapter.sockets, &frames)
+        .unwrap();
+    assert_eq!(sent, frames.len());
+    net.run(50);
+    assert_eq!(net.recv(), bytes);
+}
+
+#[test]
+fn tcp_heartbeats() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.handshake();
+    net.run(1000);
+
+    let bytes = net.recv();
+    assert_eq!(bytes.len(), FRAME_LEN);
+
+    let frame = Frame::read_from(&bytes[..]).unwrap();
+    match Message::from_frame(frame, BusNumber::default()) {
+        Ok(Message::Heartbeat(heartbeat)) => {
+            assert_eq!(heartbeat.mac_addr, mac_addr(ADAPTER).0);
+            assert_eq!(heartbeat.data_rate, Bitrate::Kbps500);
+        }
+        other => panic!("expected heartbeat, got {other:?}"),
+    }
+}
+
+#[test]
+fn tcp_handshake_failed() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.connect();
+    net.events();
+
+    // a header without the protocol version
+    let mut header = Packet::new_stream_header(&BusNumber::default());
+    header.header = Header::new();
+    net.send(header.as_bytes());
+
+    assert_eq!(
+        net.events(),
+        [Event::HandshakeFailed, Event::ClientDisconnected]
+    );
+    assert_eq!(net.server.stats().wrong_version, 1);
+    assert!(!net.client().is_active());
+}
+
+#[test]
+fn tcp_reconnect() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.handshake();
+
+    net.client().close();
+    net.run(50);
+    assert_eq!(
+        net.events(),
+        [
+            Event::ClientConnected {
+                remote: IpEndpoint::new(ip_addr(CLIENT), net.local_port)
+            },
+            Event::ClientDisconnected
+        ]
+    );
+
+    net.client().abort();
+    net.run(1);
+    net.handshake();
+
+    let remote = IpEndpoint::new(ip_addr(CLIENT), net.local_port);
+    assert_eq!(net.events(), [Event::ClientConnected { remote }]);
+    assert_eq!(net.server.stats().reconnects, 1);
+
+    // frames flow on the new connection
+    net.send(frame(0x10, &[1]).as_bytes());
+    let received = net.server.recv_frame(&mut net.adapter.sockets).unwrap();
+    assert_eq!(received.map(|r| r.frame), Some(frame(0x10, &[1])));
+}
+
+#[test]
+fn tcp_bus_change() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.handshake();
+    net.events();
+
+    // the stream header cannot be resent, so the connection is closed
+    net.server.set_bus_number(&mut net.adapter.sockets, bus(2));
+    net.run(50);
+
+    assert!(!net.client().may_recv());
+    net.client().close();
+    net.run(50);
+    assert_eq!(net.events(), [Event::ClientDisconnected]);
+    assert_eq!(net.server.bus_number(), bus(2));
+
+    // a new connection is announced with the new bus number
+    net.client().abort();
+    net.run(1);
+    net.connect();
+    let header = net.recv();
+    let header = Packet::read_from(&header[..]).unwrap().header;
+    assert_eq!(header, Header::with_bus_number(&bus(2)));
+}
+
+#[test]
+fn tcp_full_buffer() {
+    let mut net = TcpNet::new();
+    net.run(1);
+    net.handshake();
+
+    let mut sent = 0;
+    while net
+        .server
+        .send_frame(&mut net.adapter.sockets, &frame(0x10, &[1]))
+        .is_ok()
+    {
+        sent += 1;
+    }
+
+    // the heartbeat does not fit in the space left, so none of it is sent
+    assert!(net.server.send_heartbeat(&mut net.adapter.sockets).is_err());
+    assert_eq!(net.server.stats().heartbeats_tx, 0);
+
+    net.run(50);
+    let mut bytes = net.recv();
+    net.run(50);
+    bytes.extend(net.recv());
+    assert_eq!(bytes.len(), sent * FRAME_LEN);
+    for chunk in bytes.chunks(FRAME_LEN) {
+        assert_eq!(chunk, frame(0x10, &[1]).as_bytes());
+    }
+}
@@ -0,0 +1,21 @@
+//! `std::net` drivers for the Tritium CAN protocol.
+//!
+//! This crate provides adapter (server) and client implementations for the
+//! protocol used by the Tritium CAN-Ethernet adapter, for use on host
+//! machines. The same protocol code is shared with the embedded drivers.
+
+pub mod tcp;
+pub mod udp;
+
+// re-export
+pub use tritiumcan as proto;
+
+use std::io;
+
+/// Maximum number of adapters a client tracks heartbeats from.
+pub const MAX_ADAPTERS: usize = 16;
+
+/// Error returned when a frame can not be encoded.
+pub(crate) fn invalid_frame() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "frame longer than 8 bytes")
+}
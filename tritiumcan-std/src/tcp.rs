@@ -0,0 +1,303 @@
+//! TCP protocol.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::invalid_frame;
+use tritiumcan::{
+    datagram::{DecodeError, Frame, Message, Packet},
+    link::{self, Monitor},
+    stream::{Decoder, Item, HEADER_LEN},
+    Bitrate, BusNumber, HEARTBEAT_INTERVAL,
+};
+use zerocopy::AsBytes;
+
+/// Adapter instance, accepting client connections.
+#[derive(Debug)]
+pub struct Server {
+    // configuration
+    listener: TcpListener,
+    mac_addr: [u8; 6],
+    bus_number: BusNumber,
+    data_rate: Bitrate,
+}
+
+impl Server {
+    /// Creates a new [`Server`] listening on `addr`.
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        mac_addr: [u8; 6],
+        bus_number: BusNumber,
+        data_rate: Bitrate,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            mac_addr,
+            bus_number,
+            data_rate,
+        })
+    }
+
+    /// Local address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept a client and exchange stream headers.
+    pub fn accept(&self) -> io::Result<Connection> {
+        let (stream, _) = self.listener.accept()?;
+
+        let heartbeat = Packet::new_heartbeat(
+            &self.mac_addr,
+            &self.bus_number,
+            &self.data_rate,
+        );
+
+        Connection::handshake(stream, self.bus_number, Some(heartbeat.frame))
+    }
+}
+
+/// An open TCP stream, from either end of the connection.
+#[derive(Debug)]
+pub struct Connection {
+    // configuration
+    stream: TcpStream,
+    heartbeat: Option<Frame>,
+
+    // state
+    decoder: Decoder,
+    buf: [u8; HEADER_LEN],
+    len: usize,
+    last_heartbeat: Option<Instant>,
+    link: Monitor<1>,
+    epoch: Instant,
+}
+
+impl Connection {
+    /// Connect to an adapter and exchange stream headers.
+    pub fn connect(
+        addr: impl ToSocketAddrs,
+        bus_number: BusNumber,
+    ) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+
+        Self::handshake(stream, bus_number, None)
+    }
+
+    fn handshake(
+        mut stream: TcpStream,
+        bus_number: BusNumber,
+        heartbeat: Option<Frame>,
+    ) -> io::Result<Self> {
+        stream.write_all(Packet::new_stream_header(&bus_number).as_bytes())?;
+
+        let mut connection = Self {
+            stream,
+            heartbeat,
+            decoder: Decoder::new(),
+            buf: [0; HEADER_LEN],
+            len: 0,
+            last_heartbeat: None,
+            link: Monitor::default(),
+            epoch: Instant::now(),
+        };
+
+        match connection.next_item()? {
+            Item::Header(_) => Ok(connection),
+            Item::Message(_) => unreachable!(),
+        }
+    }
+
+    /// Bus number announced by the remote end.
+    pub fn remote_bus_number(&self) -> BusNumber {
+        // always set once the handshake has completed
+        self.decoder.bus_number().unwrap()
+    }
+
+    /// Address of the remote end.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Set the timeout for receive operations, `None` blocks indefinitely.
+    ///
+    /// Partially received frames are kept when the timeout expires.
+    pub fn set_read_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    /// Set the number of missed heartbeats before the link is reported lost.
+    pub fn set_max_missed_heartbeats(&mut self, max_missed: u32) {
+        self.link.set_max_missed(max_missed);
+    }
+
+    /// Set the interval adapters are expected to send heartbeats at, for
+    /// adapters configured with a non-default interval.
+    pub fn set_heartbeat_interval(&mut self, interval: Duration) {
+        self.link.set_interval(interval);
+    }
+
+    /// Check whether the link to the adapter was lost or restored.
+    ///
+    /// Heartbeats are recorded as they are received, so use a read timeout
+    /// to keep calling this while the connection is quiet.
+    pub fn poll_link(&mut self) -> Option<link::Event> {
+        self.link.poll(self.epoch.elapsed())
+    }
+
+    /// Send a heartbeat if one is due.
+    ///
+    /// Only connections accepted by a [`Server`] send heartbeats.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let Some(heartbeat) = self.heartbeat else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+
+        let due = match self.last_heartbeat {
+            Some(last) => now - last >= HEARTBEAT_INTERVAL,
+            None => true,
+        };
+
+        if due {
+            self.stream.write_all(heartbeat.as_bytes())?;
+            self.last_heartbeat = Some(now);
+        }
+
+        Ok(())
+    }
+
+    /// Send a CAN frame.
+    pub fn send_frame(
+        &mut self,
+        frame: &impl embedded_can::Frame,
+    ) -> io::Result<()> {
+        let frame = Frame::from_frame(frame).ok_or_else(invalid_frame)?;
+
+        self.stream.write_all(frame.as_bytes())
+    }
+
+    /// Receive the next message, skipping malformed frames.
+    pub fn recv(&mut self) -> io::Result<Message> {
+        loop {
+            if let Item::Message(message) = self.next_item()? {
+                if let Message::Heartbeat(heartbeat) = message {
+                    self.link
+                        .heartbeat(&heartbeat.mac_addr, self.epoch.elapsed());
+                }
+
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Receive a CAN frame, blocking until one arrives or the read timeout
+    /// expires.
+    pub fn recv_frame(&mut self) -> io::Result<Frame> {
+        loop {
+            if let Message::Frame(frame) = self.recv()? {
+                return Ok(frame);
+            }
+        }
+    }
+
+    fn next_item(&mut self) -> io::Result<Item> {
+        loop {
+            if let Some((len, item)) =
+                self.decoder.decode(&self.buf[..self.len])
+            {
+                self.buf.copy_within(len..self.len, 0);
+                self.len -= len;
+
+                match item {
+                    Ok(item) => return Ok(item),
+                    Err(DecodeError::Frame) => continue,
+                    Err(err) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid stream header: {err:?}"),
+                        ))
+                    }
+                }
+            }
+
+            match self.stream.read(&mut self.buf[self.len..])? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                len => self.len += len,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::{ExtendedId, Frame as CanFrame};
+    use std::thread;
+    use tritiumcan::datagram::Heartbeat;
+
+    #[test]
+    fn client_server() {
+        let bus_number = BusNumber::try_from(7).unwrap();
+        let mac_addr = [0x02, 0, 0, 0, 0, 2];
+        let server = Server::bind(
+            "127.0.0.1:0",
+            mac_addr,
+            bus_number,
+            Bitrate::Kbps1000,
+        )
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let id = ExtendedId::new(0x1234567).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[0xDE, 0xAD]).unwrap();
+
+        let client = thread::spawn(move || {
+            let mut client =
+                Connection::connect(addr, BusNumber::default()).unwrap();
+            assert_eq!(client.remote_bus_number(), bus_number);
+
+            assert_eq!(
+                client.recv().unwrap(),
+                Message::Heartbeat(Heartbeat {
+                    bus_number,
+                    data_rate: Bitrate::Kbps1000,
+                    mac_addr,
+                })
+            );
+            assert_eq!(client.recv_frame().unwrap(), frame);
+
+            client.send_frame(&frame).unwrap();
+        });
+
+        let mut connection = server.accept().unwrap();
+        assert_eq!(connection.remote_bus_number(), BusNumber::default());
+        connection.poll().unwrap();
+        connection.send_frame(&frame).unwrap();
+        assert_eq!(connection.recv_frame().unwrap(), frame);
+
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn bad_header() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let peer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[0; HEADER_LEN]).unwrap();
+        });
+
+        let err = Connection::connect(addr, BusNumber::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        peer.join().unwrap();
+    }
+}
@@ -0,0 +1,453 @@
+//! UDP protocol.
+
+use std::io;
+use std::mem::size_of;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::{invalid_frame, MAX_ADAPTERS};
+use socket2::{Domain, Protocol, Type};
+use tritiumcan::{
+    datagram::{Conflict, Frame, Heartbeat, Message, Packet},
+    link::{self, Monitor},
+    Bitrate, BusNumber, BROADCAST, HEARTBEAT_INTERVAL, PORT,
+};
+use zerocopy::FromBytes;
+
+/// Socket configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Local address to bind to.
+    pub bind: SocketAddr,
+    /// Address frames and heartbeats are sent to.
+    pub destination: SocketAddr,
+    /// Multicast group to join and the local interface to join it on.
+    pub multicast: Option<(Ipv4Addr, Ipv4Addr)>,
+}
+
+impl Default for Config {
+    /// Bind to [`PORT`] on all interfaces and use the [`BROADCAST`] group.
+    fn default() -> Self {
+        let group = match BROADCAST {
+            IpAddr::V4(addr) => addr,
+            _ => unreachable!(),
+        };
+
+        Self {
+            bind: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), PORT),
+            destination: SocketAddr::new(BROADCAST, PORT),
+            multicast: Some((group, Ipv4Addr::UNSPECIFIED)),
+        }
+    }
+}
+
+/// Socket shared by the server and client.
+#[derive(Debug)]
+struct Socket {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    bus_number: BusNumber,
+}
+
+impl Socket {
+    fn bind(config: &Config, bus_number: BusNumber) -> io::Result<Self> {
+        let socket = socket2::Socket::new(
+            Domain::for_address(config.bind),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+        // allow adapters and clients on the same host to share the port
+        socket.set_reuse_address(true)?;
+        socket.bind(&config.bind.into())?;
+
+        if let Some((group, interface)) = config.multicast {
+            socket.join_multicast_v4(&group, &interface)?;
+            socket.set_multicast_loop_v4(true)?;
+        }
+
+        Ok(Self {
+            socket: socket.into(),
+            destination: config.destination,
+            bus_number,
+        })
+    }
+
+    fn send_packet(&self, packet: &Packet) -> io::Result<()> {
+        self.socket
+            .send_to(packet.as_bytes(), self.destination)
+            .map(|_| ())
+    }
+
+    fn send_frame(&self, frame: &impl embedded_can::Frame) -> io::Result<()> {
+        let packet = Packet::new_frame(&self.bus_number, frame)
+            .ok_or_else(invalid_frame)?;
+
+        self.send_packet(&packet)
+    }
+
+    /// Receive the next valid message, skipping malformed datagrams and
+    /// frames for other buses.
+    fn recv_from(&self) -> io::Result<(Message, SocketAddr)> {
+        let mut buf = [0u8; size_of::<Packet>()];
+
+        loop {
+            let (len, addr) = self.socket.recv_from(&mut buf)?;
+
+            let Some(packet) = Packet::read_from(&buf[..len]) else {
+                continue;
+            };
+
+            match packet.message() {
+                Ok(message @ Message::Heartbeat(_)) => {
+                    return Ok((message, addr))
+                }
+                Ok(message)
+                    if packet.header.bus_number()
+                        == u8::from(self.bus_number) =>
+                {
+                    return Ok((message, addr))
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// Adapter instance, sending heartbeats and bridging frames.
+#[derive(Debug)]
+pub struct Server {
+    // configuration
+    socket: Socket,
+    mac_addr: [u8; 6],
+    data_rate: Bitrate,
+
+    // state
+    last_heartbeat: Option<Instant>,
+    conflict: Option<Conflict>,
+}
+
+impl Server {
+    /// Creates a new [`Server`] bound according to `config`.
+    pub fn bind(
+        config: &Config,
+        mac_addr: [u8; 6],
+        bus_number: BusNumber,
+        data_rate: Bitrate,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            socket: Socket::bind(config, bus_number)?,
+            mac_addr,
+            data_rate,
+            last_heartbeat: None,
+            conflict: None,
+        })
+    }
+
+    /// Get the current bus number.
+    pub fn bus_number(&self) -> BusNumber {
+        self.socket.bus_number
+    }
+
+    /// Set a new bus number.
+    pub fn set_bus_number(&mut self, bus_number: BusNumber) {
+        self.socket.bus_number = bus_number;
+    }
+
+    /// Set the address frames and heartbeats are sent to.
+    pub fn set_destination(&mut self, destination: SocketAddr) {
+        self.socket.destination = destination;
+    }
+
+    /// Local address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.socket.local_addr()
+    }
+
+    /// Set the timeout for receive operations, `None` blocks indefinitely.
+    pub fn set_read_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        self.socket.socket.set_read_timeout(timeout)
+    }
+
+    /// Take the most recent conflict with another adapter on the network.
+    ///
+    /// Conflicts are detected from heartbeats passing through
+    /// [`Server::recv_frame`].
+    pub fn take_conflict(&mut self) -> Option<Conflict> {
+        self.conflict.take()
+    }
+
+    /// Send a heartbeat if one is due.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+
+        let due = match self.last_heartbeat {
+            Some(last) => now - last >= HEARTBEAT_INTERVAL,
+            None => true,
+        };
+
+        if due {
+            self.send_heartbeat()?;
+            self.last_heartbeat = Some(now);
+        }
+
+        Ok(())
+    }
+
+    /// Broadcast heartbeat.
+    ///
+    /// Note: this doesn't reset the heartbeat interval.
+    pub fn send_heartbeat(&self) -> io::Result<()> {
+        let packet = Packet::new_heartbeat(
+            &self.mac_addr,
+            &self.socket.bus_number,
+            &self.data_rate,
+        );
+
+        self.socket.send_packet(&packet)
+    }
+
+    /// Broadcast a CAN frame.
+    pub fn send_frame(
+        &self,
+        frame: &impl embedded_can::Frame,
+    ) -> io::Result<()> {
+        self.socket.send_frame(frame)
+    }
+
+    /// Receive a CAN frame for this bus, blocking until one arrives or the
+    /// read timeout expires.
+    ///
+    /// Heartbeats from other adapters are consumed and checked for conflicts.
+    pub fn recv_frame(&mut self) -> io::Result<Frame> {
+        loop {
+            match self.socket.recv_from()? {
+                (Message::Frame(frame), _) => return Ok(frame),
+                (Message::Heartbeat(heartbeat), _) => {
+                    let own = Heartbeat {
+                        bus_number: self.socket.bus_number,
+                        data_rate: self.data_rate,
+                        mac_addr: self.mac_addr,
+                    };
+
+                    if let Some(conflict) = own.conflict(&heartbeat) {
+                        self.conflict = Some(conflict);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Client instance, exchanging frames with adapters.
+#[derive(Debug)]
+pub struct Client {
+    // configuration
+    socket: Socket,
+
+    // state
+    link: Monitor<MAX_ADAPTERS>,
+    epoch: Instant,
+}
+
+impl Client {
+    /// Creates a new [`Client`] bound according to `config`.
+    pub fn bind(config: &Config, bus_number: BusNumber) -> io::Result<Self> {
+        Ok(Self {
+            socket: Socket::bind(config, bus_number)?,
+            link: Monitor::default(),
+            epoch: Instant::now(),
+        })
+    }
+
+    /// Get the current bus number.
+    pub fn bus_number(&self) -> BusNumber {
+        self.socket.bus_number
+    }
+
+    /// Set a new bus number.
+    pub fn set_bus_number(&mut self, bus_number: BusNumber) {
+        self.socket.bus_number = bus_number;
+    }
+
+    /// Set the address frames are sent to.
+    pub fn set_destination(&mut self, destination: SocketAddr) {
+        self.socket.destination = destination;
+    }
+
+    /// Local address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.socket.local_addr()
+    }
+
+    /// Set the timeout for receive operations, `None` blocks indefinitely.
+    pub fn set_read_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        self.socket.socket.set_read_timeout(timeout)
+    }
+
+    /// Send a CAN frame.
+    pub fn send_frame(
+        &self,
+        frame: &impl embedded_can::Frame,
+    ) -> io::Result<()> {
+        self.socket.send_frame(frame)
+    }
+
+    /// Set the number of missed heartbeats before a link is reported lost.
+    pub fn set_max_missed_heartbeats(&mut self, max_missed: u32) {
+        self.link.set_max_missed(max_missed);
+    }
+
+    /// Set the interval adapters are expected to send heartbeats at, for
+    /// adapters configured with a non-default interval.
+    pub fn set_heartbeat_interval(&mut self, interval: Duration) {
+        self.link.set_interval(interval);
+    }
+
+    /// Check for adapters whose link was lost or restored.
+    ///
+    /// Heartbeats are recorded as they are received, so use a read timeout
+    /// to keep calling this while the network is quiet. Returns one event
+    /// per call, call until `None` is returned.
+    pub fn poll_link(&mut self) -> Option<link::Event> {
+        self.link.poll(self.epoch.elapsed())
+    }
+
+    /// Receive the next message and the address it was sent from.
+    ///
+    /// Heartbeats are returned for every bus, other messages only for the
+    /// client's bus.
+    pub fn recv_from(&mut self) -> io::Result<(Message, SocketAddr)> {
+        let (message, addr) = self.socket.recv_from()?;
+
+        if let Message::Heartbeat(heartbeat) = message {
+            self.link
+                .heartbeat(&heartbeat.mac_addr, self.epoch.elapsed());
+        }
+
+        Ok((message, addr))
+    }
+
+    /// Receive a CAN frame for this bus, blocking until one arrives or the
+    /// read timeout expires.
+    pub fn recv_frame(&mut self) -> io::Result<Frame> {
+        loop {
+            if let (Message::Frame(frame), _) = self.recv_from()? {
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::{Frame as CanFrame, StandardId};
+    use tritiumcan::datagram::Heartbeat;
+
+    fn loopback() -> Config {
+        let localhost = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+
+        Config {
+            bind: localhost,
+            destination: localhost,
+            multicast: None,
+        }
+    }
+
+    #[test]
+    fn server_to_client() {
+        let bus_number = BusNumber::try_from(4).unwrap();
+        let mac_addr = [0x02, 0, 0, 0, 0, 1];
+
+        let mut server =
+            Server::bind(&loopback(), mac_addr, bus_number, Bitrate::Kbps500)
+                .unwrap();
+        let mut client = Client::bind(&loopback(), bus_number).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        server.set_destination(client.local_addr().unwrap());
+
+        server.poll().unwrap();
+        let (message, addr) = client.recv_from().unwrap();
+        assert_eq!(addr, server.local_addr().unwrap());
+        assert_eq!(
+            message,
+            Message::Heartbeat(Heartbeat {
+                bus_number,
+                data_rate: Bitrate::Kbps500,
+                mac_addr,
+            })
+        );
+
+        let id = StandardId::new(0x123).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[1, 2, 3]).unwrap();
+        server.send_frame(&frame).unwrap();
+        assert_eq!(client.recv_frame().unwrap(), frame);
+    }
+
+    #[test]
+    fn conflict() {
+        let bus_number = BusNumber::default();
+        let mut server = Server::bind(
+            &loopback(),
+            [0x02, 0, 0, 0, 0, 1],
+            bus_number,
+            Bitrate::Kbps500,
+        )
+        .unwrap();
+        let mut other = Server::bind(
+            &loopback(),
+            [0x02, 0, 0, 0, 0, 2],
+            bus_number,
+            Bitrate::Kbps250,
+        )
+        .unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        other.set_destination(server.local_addr().unwrap());
+
+        other.send_heartbeat().unwrap();
+        assert!(server.recv_frame().is_err());
+        assert_eq!(
+            server.take_conflict(),
+            Some(Conflict::DataRate {
+                mac_addr: [0x02, 0, 0, 0, 0, 2],
+                data_rate: Bitrate::Kbps250
+            })
+        );
+        assert_eq!(server.take_conflict(), None);
+    }
+
+    #[test]
+    fn other_bus_ignored() {
+        let mut client =
+            Client::bind(&loopback(), BusNumber::try_from(1).unwrap()).unwrap();
+        let mut server = Server::bind(
+            &loopback(),
+            [0; 6],
+            BusNumber::try_from(2).unwrap(),
+            Bitrate::Kbps250,
+        )
+        .unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        client.set_destination(server.local_addr().unwrap());
+
+        let id = StandardId::new(0x10).unwrap();
+        client
+            .send_frame(&<Frame as CanFrame>::new(id, &[]).unwrap())
+            .unwrap();
+        assert!(server.recv_frame().is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! TCP stream codec.
+
+use std::io;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+use tritiumcan::{
+    datagram::{DecodeError, Frame, Header, Packet},
+    stream::{self, Item, HEADER_LEN},
+};
+use zerocopy::AsBytes;
+
+/// Codec for the TCP stream format.
+///
+/// Decodes the stream header followed by messages, and encodes either a
+/// stream [`Header`] or a [`Frame`].
+#[derive(Debug, Default)]
+pub struct Codec {
+    decoder: stream::Decoder,
+}
+
+impl Codec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stream decoder state, including the remote bus number once the header
+    /// has been received.
+    pub fn decoder(&self) -> &stream::Decoder {
+        &self.decoder
+    }
+}
+
+impl Decoder for Codec {
+    type Item = Item;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Item>> {
+        loop {
+            let Some((len, item)) = self.decoder.decode(src) else {
+                src.reserve(HEADER_LEN);
+                return Ok(None);
+            };
+
+            src.advance(len);
+
+            match item {
+                Ok(item) => return Ok(Some(item)),
+                // skip malformed frames
+                Err(DecodeError::Frame) => continue,
+                Err(err) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid stream header: {err:?}"),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl Encoder<Header> for Codec {
+    type Error = io::Error;
+
+    fn encode(&mut self, header: Header, dst: &mut BytesMut) -> io::Result<()> {
+        let packet = Packet {
+            header,
+            frame: Frame::new(),
+        };
+
+        dst.put_slice(packet.as_bytes());
+        Ok(())
+    }
+}
+
+impl Encoder<Frame> for Codec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> io::Result<()> {
+        dst.put_slice(frame.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::{Frame as CanFrame, StandardId};
+    use tritiumcan::{datagram::Message, BusNumber};
+
+    #[test]
+    fn round_trip() {
+        let bus_number = BusNumber::try_from(9).unwrap();
+        let header = Header::with_bus_number(&bus_number);
+        let id = StandardId::new(0x7FF).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[1, 2, 3, 4]).unwrap();
+
+        let mut codec = Codec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(header, &mut buf).unwrap();
+        codec.encode(frame, &mut buf).unwrap();
+
+        // partial frame
+        let mut partial = buf.split_to(HEADER_LEN + 3);
+        assert_eq!(
+            codec.decode(&mut partial).unwrap(),
+            Some(Item::Header(header))
+        );
+        assert_eq!(codec.decode(&mut partial).unwrap(), None);
+        assert_eq!(codec.decoder().bus_number(), Some(bus_number));
+
+        partial.unsplit(buf);
+        assert_eq!(
+            codec.decode(&mut partial).unwrap(),
+            Some(Item::Message(Message::Frame(frame)))
+        );
+        assert!(partial.is_empty());
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut buf = BytesMut::from(&[0u8; HEADER_LEN][..]);
+        let err = Codec::new().decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,15 @@
+//! tokio drivers for the Tritium CAN protocol.
+//!
+//! This crate provides a [`tokio_util::codec`] implementation of the TCP
+//! stream format and an async UDP client, for host services consuming traffic
+//! from Tritium CAN-Ethernet adapters.
+
+pub mod codec;
+pub mod tcp;
+pub mod udp;
+
+// re-export
+pub use tritiumcan as proto;
+
+/// Maximum number of adapters a client tracks heartbeats from.
+pub const MAX_ADAPTERS: usize = 16;
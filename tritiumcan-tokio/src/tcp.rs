@@ -0,0 +1,191 @@
+//! TCP protocol.
+
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use crate::codec::Codec;
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_util::codec::Framed;
+use tritiumcan::{
+    datagram::{Frame, Header, Message},
+    stream::Item,
+    BusNumber,
+};
+
+/// Connect to an adapter and exchange stream headers.
+pub async fn connect(
+    addr: impl ToSocketAddrs,
+    bus_number: BusNumber,
+) -> io::Result<Connection<TcpStream>> {
+    let stream = TcpStream::connect(addr).await?;
+
+    handshake(stream, bus_number).await
+}
+
+/// Exchange stream headers on an already open connection.
+///
+/// Used to serve clients from a listener as well as by [`connect`].
+pub async fn handshake<T>(
+    io: T,
+    bus_number: BusNumber,
+) -> io::Result<Connection<T>>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(io, Codec::new());
+
+    let header = Header::with_bus_number(&bus_number);
+    poll_fn(|cx| Sink::<Header>::poll_ready(Pin::new(&mut framed), cx)).await?;
+    Pin::new(&mut framed).start_send(header)?;
+    poll_fn(|cx| Sink::<Header>::poll_flush(Pin::new(&mut framed), cx)).await?;
+
+    match poll_fn(|cx| Pin::new(&mut framed).poll_next(cx)).await {
+        Some(Ok(Item::Header(_))) => Ok(Connection { framed }),
+        Some(Ok(Item::Message(_))) => unreachable!(),
+        Some(Err(err)) => Err(err),
+        None => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+/// An open TCP stream, after the stream headers have been exchanged.
+///
+/// Implements [`Stream`] of received frames and [`Sink`] of frames to send.
+/// The stream skips heartbeats and settings, use [`Connection::recv`] to
+/// receive every message.
+#[derive(Debug)]
+pub struct Connection<T> {
+    framed: Framed<T, Codec>,
+}
+
+impl<T> Connection<T> {
+    /// Bus number announced in the remote stream header.
+    pub fn remote_bus_number(&self) -> BusNumber {
+        // always set once the handshake has completed
+        self.framed.codec().decoder().bus_number().unwrap()
+    }
+
+    /// Underlying I/O stream.
+    pub fn get_ref(&self) -> &T {
+        self.framed.get_ref()
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
+    /// Receive the next message.
+    ///
+    /// Fails with [`io::ErrorKind::UnexpectedEof`] once the remote end has
+    /// closed the connection.
+    pub async fn recv(&mut self) -> io::Result<Message> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Poll version of [`Connection::recv`].
+    pub fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Message>> {
+        match ready!(Pin::new(&mut self.framed).poll_next(cx)) {
+            Some(Ok(Item::Message(message))) => Poll::Ready(Ok(message)),
+            // the decoder only yields the header before any message
+            Some(Ok(Item::Header(_))) => unreachable!(),
+            Some(Err(err)) => Poll::Ready(Err(err)),
+            None => Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Stream for Connection<T> {
+    type Item = io::Result<Frame>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match ready!(Pin::new(&mut this.framed).poll_next(cx)) {
+                Some(Ok(Item::Message(Message::Frame(frame)))) => {
+                    return Poll::Ready(Some(Ok(frame)))
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Sink<Frame> for Connection<T> {
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Sink::<Frame>::poll_ready(Pin::new(&mut self.get_mut().framed), cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, frame: Frame) -> io::Result<()> {
+        Pin::new(&mut self.get_mut().framed).start_send(frame)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Sink::<Frame>::poll_flush(Pin::new(&mut self.get_mut().framed), cx)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Sink::<Frame>::poll_close(Pin::new(&mut self.get_mut().framed), cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::{Frame as CanFrame, StandardId};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tritiumcan::{datagram::Packet, Bitrate};
+
+    #[tokio::test]
+    async fn client_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let id = StandardId::new(0x55).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[9, 8, 7]).unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let bus_number = BusNumber::try_from(1).unwrap();
+            let mut server = handshake(stream, bus_number).await.unwrap();
+
+            // heartbeats are not frames
+            let heartbeat = Packet::new_heartbeat(
+                &[0x02, 0, 0, 0, 0, 1],
+                &bus_number,
+                &Bitrate::Kbps500,
+            );
+            server.send(heartbeat.frame).await.unwrap();
+            server.send(frame).await.unwrap();
+        });
+
+        let mut client = connect(addr, BusNumber::default()).await.unwrap();
+        assert_eq!(client.remote_bus_number(), BusNumber::try_from(1).unwrap());
+        assert_eq!(client.next().await.unwrap().unwrap(), frame);
+        assert!(client.next().await.is_none());
+
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,292 @@
+//! UDP protocol.
+
+use std::io;
+use std::mem::size_of;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::MAX_ADAPTERS;
+use futures_core::Stream;
+use futures_sink::Sink;
+use socket2::{Domain, Protocol, Type};
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+use tritiumcan::{
+    datagram::{Frame, Header, Message, Packet},
+    link::{self, Monitor},
+    BusNumber, BROADCAST, PORT,
+};
+use zerocopy::FromBytes;
+
+/// Socket configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Local address to bind to.
+    pub bind: SocketAddr,
+    /// Address frames are sent to.
+    pub destination: SocketAddr,
+    /// Multicast group to join and the local interface to join it on.
+    pub multicast: Option<(Ipv4Addr, Ipv4Addr)>,
+}
+
+impl Default for Config {
+    /// Bind to [`PORT`] on all interfaces and use the [`BROADCAST`] group.
+    fn default() -> Self {
+        let group = match BROADCAST {
+            IpAddr::V4(addr) => addr,
+            _ => unreachable!(),
+        };
+
+        Self {
+            bind: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), PORT),
+            destination: SocketAddr::new(BROADCAST, PORT),
+            multicast: Some((group, Ipv4Addr::UNSPECIFIED)),
+        }
+    }
+}
+
+/// Async client instance.
+///
+/// Implements [`Stream`] of received frames for the client's bus and
+/// [`Sink`] of frames to send.
+#[derive(Debug)]
+pub struct Client {
+    // configuration
+    socket: UdpSocket,
+    destination: SocketAddr,
+    bus_number: BusNumber,
+
+    // state
+    rx_buffer: [u8; size_of::<Packet>()],
+    pending: Option<Packet>,
+    link: Monitor<MAX_ADAPTERS>,
+    epoch: Instant,
+}
+
+impl Client {
+    /// Creates a new [`Client`] bound according to `config`.
+    pub fn bind(config: &Config, bus_number: BusNumber) -> io::Result<Self> {
+        let socket = socket2::Socket::new(
+            Domain::for_address(config.bind),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+        // allow adapters and clients on the same host to share the port
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&config.bind.into())?;
+
+        if let Some((group, interface)) = config.multicast {
+            socket.join_multicast_v4(&group, &interface)?;
+            socket.set_multicast_loop_v4(true)?;
+        }
+
+        Ok(Self {
+            socket: UdpSocket::from_std(socket.into())?,
+            destination: config.destination,
+            bus_number,
+            rx_buffer: [0; size_of::<Packet>()],
+            pending: None,
+            link: Monitor::default(),
+            epoch: Instant::now(),
+        })
+    }
+
+    /// Get the current bus number.
+    pub fn bus_number(&self) -> BusNumber {
+        self.bus_number
+    }
+
+    /// Set a new bus number.
+    pub fn set_bus_number(&mut self, bus_number: BusNumber) {
+        self.bus_number = bus_number;
+    }
+
+    /// Set the address frames are sent to.
+    pub fn set_destination(&mut self, destination: SocketAddr) {
+        self.destination = destination;
+    }
+
+    /// Local address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Set the number of missed heartbeats before a link is reported lost.
+    pub fn set_max_missed_heartbeats(&mut self, max_missed: u32) {
+        self.link.set_max_missed(max_missed);
+    }
+
+    /// Set the interval adapters are expected to send heartbeats at, for
+    /// adapters configured with a non-default interval.
+    pub fn set_heartbeat_interval(&mut self, interval: Duration) {
+        self.link.set_interval(interval);
+    }
+
+    /// Check for adapters whose link was lost or restored.
+    ///
+    /// Heartbeats are recorded as they are received, call this periodically
+    /// while the network is quiet. Returns one event per call, call until
+    /// `None` is returned.
+    pub fn poll_link(&mut self) -> Option<link::Event> {
+        self.link.poll(self.epoch.elapsed())
+    }
+
+    /// Receive the next message and the address it was sent from.
+    ///
+    /// Heartbeats are returned for every bus, other messages only for the
+    /// client's bus.
+    pub async fn recv_from(&mut self) -> io::Result<(Message, SocketAddr)> {
+        std::future::poll_fn(|cx| self.poll_recv_from(cx)).await
+    }
+
+    /// Poll version of [`Client::recv_from`].
+    pub fn poll_recv_from(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(Message, SocketAddr)>> {
+        loop {
+            let mut buf = ReadBuf::new(&mut self.rx_buffer);
+            let addr = ready!(self.socket.poll_recv_from(cx, &mut buf))?;
+
+            let Some(packet) = Packet::read_from(buf.filled()) else {
+                continue;
+            };
+
+            match packet.message() {
+                Ok(message @ Message::Heartbeat(heartbeat)) => {
+                    self.link
+                        .heartbeat(&heartbeat.mac_addr, self.epoch.elapsed());
+                    return Poll::Ready(Ok((message, addr)));
+                }
+                Ok(message)
+                    if packet.header.bus_number()
+                        == u8::from(self.bus_number) =>
+                {
+                    return Poll::Ready(Ok((message, addr)))
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Stream for Client {
+    type Item = io::Result<Frame>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match ready!(this.poll_recv_from(cx)) {
+                Ok((Message::Frame(frame), _)) => {
+                    return Poll::Ready(Some(Ok(frame)))
+                }
+                Ok(_) => continue,
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            }
+        }
+    }
+}
+
+impl Sink<Frame> for Client {
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, frame: Frame) -> io::Result<()> {
+        let this = self.get_mut();
+
+        this.pending = Some(Packet {
+            header: Header::with_bus_number(&this.bus_number),
+            frame,
+        });
+
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if let Some(packet) = &this.pending {
+            ready!(this.socket.poll_send_to(
+                cx,
+                packet.as_bytes(),
+                this.destination
+            ))?;
+            this.pending = None;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::{Frame as CanFrame, StandardId};
+    use futures_util::{SinkExt, StreamExt};
+    use tritiumcan::{datagram::Heartbeat, Bitrate};
+
+    fn loopback() -> Config {
+        let localhost = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+
+        Config {
+            bind: localhost,
+            destination: localhost,
+            multicast: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_and_sink() {
+        let bus_number = BusNumber::try_from(5).unwrap();
+        let mut tx = Client::bind(&loopback(), bus_number).unwrap();
+        let mut rx = Client::bind(&loopback(), bus_number).unwrap();
+        tx.set_destination(rx.local_addr().unwrap());
+
+        // heartbeats are not frames
+        let mac_addr = [0x02, 0, 0, 0, 0, 3];
+        let heartbeat =
+            Packet::new_heartbeat(&mac_addr, &bus_number, &Bitrate::Kbps125);
+        tx.socket
+            .send_to(heartbeat.as_bytes(), rx.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let id = StandardId::new(0x321).unwrap();
+        let frame = <Frame as CanFrame>::new(id, &[0xCA, 0xFE]).unwrap();
+        tx.send(frame).await.unwrap();
+
+        assert_eq!(
+            rx.recv_from().await.unwrap().0,
+            Message::Heartbeat(Heartbeat {
+                bus_number,
+                data_rate: Bitrate::Kbps125,
+                mac_addr,
+            })
+        );
+        assert_eq!(rx.next().await.unwrap().unwrap(), frame);
+    }
+}
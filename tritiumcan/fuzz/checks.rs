@@ -0,0 +1,160 @@
+//! Checks run by the fuzz targets, shared with the corpus replay test in
+//! `tests/fuzz_corpus.rs` so the two cannot drift apart.
+
+// each fuzz target only calls its own check
+#![allow(dead_code)]
+
+use embedded_can::{ExtendedId, Frame as CanFrame, Id, StandardId};
+use tritiumcan::{
+    datagram::{Filter, FilterBitfield, Frame, Message, Packet, FILTER_LEN},
+    filter::{Filters, Rule},
+    stream::{Decoder, Item, HEADER_LEN},
+    BusNumber,
+};
+use zerocopy::FromBytes;
+
+/// UDP datagrams of any length.
+pub fn packet(data: &[u8]) {
+    let decoded = Message::decode(data);
+
+    match Packet::read_from(data) {
+        Some(packet) => {
+            assert_eq!(packet.as_bytes(), data);
+            assert_eq!(packet.message(), decoded);
+            let _ = packet.header.version();
+            let _ = packet.header.bus_number();
+            let _ = packet.header.client_identifier();
+        }
+        None => assert!(decoded.is_err()),
+    }
+}
+
+/// Frames as read from a TCP stream, through the `embedded_can` accessors.
+pub fn frame(data: &[u8]) {
+    let Some(frame) = Frame::read_from(data) else {
+        return;
+    };
+
+    // the inherent bitfield accessors shadow the trait methods
+    let _ = CanFrame::is_extended(&frame);
+    assert!(CanFrame::data(&frame).len() <= 8);
+
+    // any frame that converts must convert back to the same CAN frame
+    if let Some(copy) = Frame::from_frame(&frame) {
+        assert_eq!(CanFrame::id(&copy), CanFrame::id(&frame));
+        assert_eq!(CanFrame::data(&copy), CanFrame::data(&frame));
+        assert_eq!(
+            CanFrame::is_remote_frame(&copy),
+            CanFrame::is_remote_frame(&frame)
+        );
+    }
+}
+
+/// Heartbeat, settings and CAN frame decoding, with the bus number taken from
+/// the first byte.
+pub fn message(data: &[u8]) {
+    let Some((&bus_number, data)) = data.split_first() else {
+        return;
+    };
+    let Ok(bus_number) = BusNumber::try_from(bus_number) else {
+        return;
+    };
+    let Some(frame) = Frame::read_from(data) else {
+        return;
+    };
+
+    match Message::from_frame(frame, bus_number) {
+        Ok(Message::Heartbeat(heartbeat)) => {
+            assert_eq!(heartbeat.bus_number, bus_number);
+            let _ = heartbeat.data_rate.bps();
+        }
+        Ok(Message::Settings(frame)) => {
+            let _ = frame.data();
+        }
+        Ok(Message::Frame(frame)) => {
+            assert!(frame.dlc() <= 8);
+            let _ = frame.id();
+        }
+        Err(_) => {}
+    }
+}
+
+/// Filter setting datagrams, decoded into an acceptance rule forwarding the
+/// identifiers from `fwd_identifier` through `fwd_range` more.
+pub fn filter(data: &[u8]) {
+    let Ok(bytes) = <[u8; FILTER_LEN]>::try_from(data) else {
+        return;
+    };
+    let filter = FilterBitfield(bytes);
+    let (start, range) = (filter.fwd_identifier(), filter.fwd_range());
+
+    let mut copy = Filter::new();
+    copy.set_fwd_identifier(start);
+    copy.set_fwd_range(range);
+    copy.set_version_number(filter.version_number());
+    copy.set_bus_number(filter.bus_number());
+    copy.set_client_identifier(filter.client_identifier());
+    assert_eq!(copy.fwd_identifier(), start);
+    assert_eq!(copy.fwd_range(), range);
+
+    let end = start.saturating_add(range);
+    let extended = end > u32::from(StandardId::MAX.as_raw());
+    let mut filters = Filters::<1>::new();
+    assert!(filters.add(Rule::Range {
+        extended,
+        start,
+        end,
+    }));
+
+    let id = |raw: u32| -> Option<Id> {
+        if extended {
+            ExtendedId::new(raw).map(Id::from)
+        } else {
+            StandardId::new(raw as u16).map(Id::from)
+        }
+    };
+    let accepts =
+        |id: Id| filters.matches(&<Frame as CanFrame>::new(id, &[]).unwrap());
+
+    for raw in [start, end] {
+        if let Some(id) = id(raw) {
+            assert!(accepts(id));
+        }
+    }
+
+    if let Some(id) = end.checked_add(1).and_then(id) {
+        assert!(!accepts(id));
+    }
+    if let Some(id) = start.checked_sub(1).and_then(id) {
+        assert!(!accepts(id));
+    }
+
+    // the other frame format is never forwarded
+    let other = if extended {
+        StandardId::new(start as u16).map(Id::from)
+    } else {
+        ExtendedId::new(start).map(Id::from)
+    };
+    if let Some(id) = other {
+        assert!(!accepts(id));
+    }
+}
+
+/// TCP streams, decoded from the start until more bytes are needed.
+pub fn stream(data: &[u8]) {
+    let mut decoder = Decoder::new();
+    let mut buf = data;
+
+    while let Some((len, item)) = decoder.decode(buf) {
+        assert!(len <= buf.len());
+        buf = &buf[len..];
+
+        match item {
+            Ok(Item::Header(_)) => assert_eq!(len, HEADER_LEN),
+            Ok(Item::Message(_)) => {}
+            // a bad stream header is not recoverable
+            Err(_) if decoder.bus_number().is_none() => break,
+            Err(_) => {}
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! Filter setting datagrams, decoded into acceptance rules.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../checks.rs"]
+mod checks;
+
+fuzz_target!(|data: &[u8]| checks::filter(data));
@@ -0,0 +1,10 @@
+//! Frames as read from a TCP stream, through the `embedded_can` accessors.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../checks.rs"]
+mod checks;
+
+fuzz_target!(|data: &[u8]| checks::frame(data));
@@ -0,0 +1,11 @@
+//! Heartbeat, settings and CAN frame decoding, with the bus number taken from
+//! the first byte.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../checks.rs"]
+mod checks;
+
+fuzz_target!(|data: &[u8]| checks::message(data));
@@ -0,0 +1,10 @@
+//! UDP datagrams of any length.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../checks.rs"]
+mod checks;
+
+fuzz_target!(|data: &[u8]| checks::packet(data));
@@ -0,0 +1,10 @@
+//! TCP streams, decoded from the start until more bytes are needed.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../checks.rs"]
+mod checks;
+
+fuzz_target!(|data: &[u8]| checks::stream(data));
@@ -0,0 +1,208 @@
+//! SocketCAN `candump -L` logs.
+//!
+//! Each line holds the time since the Unix epoch, an interface name and a
+//! frame, e.g. `(1436509052.249713) can0 123#DEADBEEF`, the format written by
+//! `candump -L` and replayed by `canplayer`. Requires the `std` feature.
+
+use crate::datagram::Frame;
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+
+/// A logged frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Time since the Unix epoch.
+    pub timestamp: Duration,
+    pub interface: String,
+    pub frame: Frame,
+}
+
+/// Formats the entry as a log line, without the line ending.
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "({}.{:06}) {} {}",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros(),
+            self.interface,
+            self.frame
+        )
+    }
+}
+
+/// candump log writer.
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Append a line to the log.
+    pub fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        writeln!(self.inner, "{entry}")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// candump log reader.
+///
+/// Blank lines are skipped, anything after the frame on a line is ignored.
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+    /// Number of lines read.
+    line: usize,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, line: 0 }
+    }
+
+    /// Read the next entry, `None` at the end of the log.
+    pub fn read(&mut self) -> io::Result<Option<Entry>> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if self.inner.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+
+        parse(&line).map(Some).map_err(|err| {
+            let message = format!("line {}: {err}", self.line);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+fn parse(line: &str) -> Result<Entry, String> {
+    let mut fields = line.split_whitespace();
+    let (Some(timestamp), Some(interface), Some(frame)) =
+        (fields.next(), fields.next(), fields.next())
+    else {
+        return Err("expected `(timestamp) interface frame`".into());
+    };
+
+    let timestamp = timestamp
+        .strip_prefix('(')
+        .and_then(|t| t.strip_suffix(')'))
+        .and_then(parse_timestamp)
+        .ok_or_else(|| format!("invalid timestamp {timestamp:?}"))?;
+    let frame = frame
+        .parse()
+        .map_err(|err| format!("invalid frame {frame:?}, {err}"))?;
+
+    Ok(Entry {
+        timestamp,
+        interface: interface.into(),
+        frame,
+    })
+}
+
+/// Parse seconds with up to nanosecond precision.
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let (secs, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let scale = 10u32.pow(9 - fraction.len() as u32);
+    let nanos = match fraction {
+        "" => 0,
+        fraction => fraction.parse::<u32>().ok()? * scale,
+    };
+
+    Some(Duration::new(secs.parse().ok()?, nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+(1436509052.249713) can0 123#DEADBEEF
+(1436509052.250000) vcan1 1ABCDEF0#R
+
+(1436509053.000001) can0 7FF#
+";
+
+    #[test]
+    fn round_trip() {
+        let entries: Vec<_> = Reader::new(LOG.as_bytes())
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].timestamp,
+            Duration::new(1436509052, 249_713_000)
+        );
+        assert_eq!(entries[1].interface, "vcan1");
+        assert_eq!(entries[2].frame, "7FF#".parse().unwrap());
+
+        let mut writer = Writer::new(vec![]);
+        for entry in &entries {
+            writer.write(entry).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            LOG.replace("\n\n", "\n")
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_timestamp("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(
+            parse_timestamp("0.000000001"),
+            Some(Duration::from_nanos(1))
+        );
+        assert_eq!(parse_timestamp("1.0000000001"), None);
+        assert_eq!(parse_timestamp("1.-5"), None);
+        assert_eq!(parse_timestamp("x"), None);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let mut reader = Reader::new("\n(1.0) can0\n".as_bytes());
+        let err = reader.read().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("line 2:"));
+
+        let mut reader = Reader::new("1.0 can0 123#00\n".as_bytes());
+        assert!(reader.read().is_err());
+
+        let mut reader = Reader::new("(1.0) can0 123#0\n".as_bytes());
+        assert!(reader.read().is_err());
+    }
+}
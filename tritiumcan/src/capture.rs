@@ -0,0 +1,771 @@
+//! pcapng captures of Tritium traffic.
+//!
+//! Packets are wrapped in synthetic Ethernet, IPv4 and UDP headers so that
+//! captures open directly in Wireshark. Captures taken elsewhere, as pcap or
+//! pcapng, can be read back as well. Requires the `std` feature.
+
+use crate::datagram::Packet;
+use std::{
+    io::{self, Read, Write},
+    net::{Ipv4Addr, SocketAddrV4},
+    time::Duration,
+};
+use zerocopy::FromBytes;
+
+const SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_END: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+
+const PCAP_MAGIC: u32 = 0xA1B2_C3D4;
+const PCAP_NANOS_MAGIC: u32 = 0xA1B2_3C4D;
+
+const LINKTYPE_ETHERNET: u16 = 1;
+const LINKTYPE_RAW: u16 = 101;
+const LINKTYPE_LINUX_SLL: u16 = 113;
+const LINKTYPE_IPV4: u16 = 228;
+const LINKTYPE_LINUX_SLL2: u16 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88A8;
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+
+const ETHERNET_LEN: usize = 14;
+const IPV4_LEN: usize = 20;
+const UDP_LEN: usize = 8;
+const TCP_LEN: usize = 20;
+/// Largest packet read, the snapshot length used by tcpdump and Wireshark.
+const MAX_PACKET_LEN: usize = 262_144;
+const PACKET_LEN: usize = size_of::<Packet>();
+
+/// Direction of a captured packet, relative to the capturing host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// A captured packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Time since the Unix epoch.
+    pub timestamp: Duration,
+    /// Direction, if known.
+    pub direction: Option<Direction>,
+    pub source: SocketAddrV4,
+    pub destination: SocketAddrV4,
+    pub packet: Packet,
+}
+
+/// pcapng capture writer.
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Start a capture, writing the section header and a single Ethernet
+    /// interface with microsecond timestamps.
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        let mut body = vec![];
+        body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+        // version 1.0, section length unknown
+        body.extend(1u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend((-1i64).to_le_bytes());
+        write_block(&mut inner, SECTION_HEADER, &body)?;
+
+        let mut body = vec![];
+        body.extend(LINKTYPE_ETHERNET.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend(u32::from(u16::MAX).to_le_bytes());
+        push_option(&mut body, OPT_IF_TSRESOL, &[6]);
+        push_option(&mut body, OPT_END, &[]);
+        write_block(&mut inner, INTERFACE_DESCRIPTION, &body)?;
+
+        Ok(Self { inner })
+    }
+
+    /// Append a packet to the capture.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let data = encapsulate(record);
+        let micros = record.timestamp.as_micros() as u64;
+
+        let mut body = vec![];
+        body.extend(0u32.to_le_bytes());
+        body.extend(((micros >> 32) as u32).to_le_bytes());
+        body.extend((micros as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend(&data);
+        pad(&mut body);
+
+        if let Some(direction) = record.direction {
+            let flags: u32 = match direction {
+                Direction::Inbound => 1,
+                Direction::Outbound => 2,
+            };
+            push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+            push_option(&mut body, OPT_END, &[]);
+        }
+
+        write_block(&mut self.inner, ENHANCED_PACKET, &body)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+fn write_block(
+    w: &mut impl Write,
+    block_type: u32,
+    body: &[u8],
+) -> io::Result<()> {
+    let len = (12 + body.len()) as u32;
+
+    w.write_all(&block_type.to_le_bytes())?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(body)?;
+    w.write_all(&len.to_le_bytes())
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend(value);
+    pad(body);
+}
+
+fn pad(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+/// Wrap a packet in Ethernet, IPv4 and UDP headers.
+fn encapsulate(record: &Record) -> Vec<u8> {
+    let (source, destination) = (record.source, record.destination);
+    let mut data = vec![];
+
+    data.extend(mac_addr(*destination.ip()));
+    data.extend(mac_addr(*source.ip()));
+    data.extend(ETHERTYPE_IPV4.to_be_bytes());
+
+    // version 4 with no options, don't fragment, TTL 64
+    let mut ip = [0u8; IPV4_LEN];
+    ip[0] = 0x45;
+    ip[2..4].copy_from_slice(
+        &((IPV4_LEN + UDP_LEN + PACKET_LEN) as u16).to_be_bytes(),
+    );
+    ip[6] = 0x40;
+    ip[8] = 64;
+    ip[9] = IP_PROTOCOL_UDP;
+    ip[12..16].copy_from_slice(&source.ip().octets());
+    ip[16..20].copy_from_slice(&destination.ip().octets());
+    let checksum = checksum(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+    data.extend(ip);
+
+    // a zero UDP checksum means none was computed
+    data.extend(source.port().to_be_bytes());
+    data.extend(destination.port().to_be_bytes());
+    data.extend(((UDP_LEN + PACKET_LEN) as u16).to_be_bytes());
+    data.extend(0u16.to_be_bytes());
+
+    data.extend(record.packet.as_bytes());
+
+    data
+}
+
+/// MAC address for an IPv4 address, the group address for multicast and a
+/// locally administered address otherwise.
+fn mac_addr(ip: Ipv4Addr) -> [u8; 6] {
+    let [a, b, c, d] = ip.octets();
+
+    if ip.is_multicast() {
+        [0x01, 0x00, 0x5E, b & 0x7F, c, d]
+    } else {
+        [0x02, 0x00, a, b, c, d]
+    }
+}
+
+/// Internet checksum of an IPv4 header.
+fn checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum::<u32>();
+
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// Capture reader.
+///
+/// Yields the Tritium packets carried over UDP, other packets and blocks are
+/// skipped. Both pcap and pcapng captures are read.
+#[derive(Debug)]
+pub struct Reader<R> {
+    source: Source<R>,
+}
+
+impl<R: Read> Reader<R> {
+    /// Open a capture, reading its file or section header.
+    pub fn new(inner: R) -> io::Result<Self> {
+        Ok(Self {
+            source: Source::new(inner)?,
+        })
+    }
+
+    /// Read the next Tritium packet, `None` at the end of the capture.
+    pub fn read(&mut self) -> io::Result<Option<Record>> {
+        while let Some(captured) = self.source.read()? {
+            let Some(segment) = parse(captured.linktype, &captured.data) else {
+                continue;
+            };
+            let Transport::Udp(payload) = segment.transport else {
+                continue;
+            };
+            let Some(packet) = Packet::read_from(payload) else {
+                continue;
+            };
+
+            return Ok(Some(Record {
+                timestamp: captured.timestamp,
+                direction: captured.direction,
+                source: segment.source,
+                destination: segment.destination,
+                packet,
+            }));
+        }
+
+        Ok(None)
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// A link layer packet read from a capture.
+#[derive(Debug)]
+pub(crate) struct Captured {
+    pub timestamp: Duration,
+    pub direction: Option<Direction>,
+    pub linktype: u16,
+    pub data: Vec<u8>,
+}
+
+/// Link layer packets of a pcap or pcapng capture.
+#[derive(Debug)]
+pub(crate) struct Source<R> {
+    inner: R,
+    big_endian: bool,
+    /// Whether this is a pcap rather than a pcapng capture.
+    legacy: bool,
+    /// Link type and timestamp units per second of each interface, pcap
+    /// captures have a single interface described by the file header.
+    interfaces: Vec<(u16, u64)>,
+}
+
+impl<R: Read> Source<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        inner
+            .read_exact(&mut magic)
+            .map_err(|_| invalid("not a pcap or pcapng capture"))?;
+
+        let mut source = Self {
+            inner,
+            big_endian: false,
+            legacy: false,
+            interfaces: vec![],
+        };
+
+        if magic == SECTION_HEADER.to_le_bytes() {
+            source.read_block_from(magic)?;
+            return Ok(source);
+        }
+
+        let (big_endian, units) = match magic {
+            m if m == PCAP_MAGIC.to_le_bytes() => (false, 1_000_000),
+            m if m == PCAP_MAGIC.to_be_bytes() => (true, 1_000_000),
+            m if m == PCAP_NANOS_MAGIC.to_le_bytes() => (false, 1_000_000_000),
+            m if m == PCAP_NANOS_MAGIC.to_be_bytes() => (true, 1_000_000_000),
+            _ => return Err(invalid("not a pcap or pcapng capture")),
+        };
+
+        // version, reserved fields and snapshot length, then the link type
+        // with the frame check sequence length in the upper bits
+        let mut header = [0; 20];
+        source.inner.read_exact(&mut header)?;
+        source.big_endian = big_endian;
+        source.legacy = true;
+        let linktype = source.u32(&header[16..20]) as u16;
+        source.interfaces.push((linktype, units));
+
+        Ok(source)
+    }
+
+    /// Read the next packet, `None` at the end of the capture.
+    pub fn read(&mut self) -> io::Result<Option<Captured>> {
+        if self.legacy {
+            return self.read_record();
+        }
+
+        while let Some((block_type, body)) = self.read_block()? {
+            match block_type {
+                SECTION_HEADER => self.interfaces.clear(),
+                INTERFACE_DESCRIPTION => {
+                    let interface = self.interface(&body)?;
+                    self.interfaces.push(interface);
+                }
+                ENHANCED_PACKET => return self.packet(&body).map(Some),
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Read a pcap packet record.
+    fn read_record(&mut self) -> io::Result<Option<Captured>> {
+        let mut head = [0; 16];
+        if !self.read_or_eof(&mut head)? {
+            return Ok(None);
+        }
+
+        let (linktype, units) = self.interfaces[0];
+        let ticks = u64::from(self.u32(&head[0..4])) * units
+            + u64::from(self.u32(&head[4..8]));
+        let captured = self.u32(&head[8..12]) as usize;
+        if captured > MAX_PACKET_LEN {
+            return Err(invalid("packet too long"));
+        }
+
+        let mut data = vec![0; captured];
+        self.inner.read_exact(&mut data)?;
+
+        Ok(Some(Captured {
+            timestamp: timestamp(ticks, units),
+            direction: None,
+            linktype,
+            data,
+        }))
+    }
+
+    /// Read a pcapng block, returning its type and body.
+    fn read_block(&mut self) -> io::Result<Option<(u32, Vec<u8>)>> {
+        let mut block_type = [0; 4];
+        if !self.read_or_eof(&mut block_type)? {
+            return Ok(None);
+        }
+
+        self.read_block_from(block_type).map(Some)
+    }
+
+    /// Read the rest of a pcapng block after its type.
+    fn read_block_from(
+        &mut self,
+        block_type: [u8; 4],
+    ) -> io::Result<(u32, Vec<u8>)> {
+        let mut len = [0; 4];
+        self.inner.read_exact(&mut len)?;
+
+        // the block type is the same in either byte order, the byte order of
+        // the section follows it
+        let mut body = vec![];
+        if block_type == SECTION_HEADER.to_le_bytes() {
+            let mut magic = [0; 4];
+            self.inner.read_exact(&mut magic)?;
+            self.big_endian = match magic {
+                m if m == BYTE_ORDER_MAGIC.to_le_bytes() => false,
+                m if m == BYTE_ORDER_MAGIC.to_be_bytes() => true,
+                _ => return Err(invalid("bad byte order magic")),
+            };
+            body.extend(magic);
+        }
+
+        let block_type = self.u32(&block_type);
+        let len = self.u32(&len) as usize;
+        if len < 12 + body.len() || !len.is_multiple_of(4) {
+            return Err(invalid("bad block length"));
+        }
+        if len > MAX_PACKET_LEN + 64 {
+            return Err(invalid("block too long"));
+        }
+
+        let start = body.len();
+        body.resize(len - 12, 0);
+        self.inner.read_exact(&mut body[start..])?;
+
+        let mut tail = [0; 4];
+        self.inner.read_exact(&mut tail)?;
+        if self.u32(&tail) as usize != len {
+            return Err(invalid("block lengths differ"));
+        }
+
+        Ok((block_type, body))
+    }
+
+    /// Fill `buf`, returning false at the end of the capture.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        match self.inner.read_exact(buf) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            result => result.map(|_| true),
+        }
+    }
+
+    fn interface(&self, body: &[u8]) -> io::Result<(u16, u64)> {
+        if body.len() < 8 {
+            return Err(invalid("short interface description"));
+        }
+
+        let linktype = self.u16(&body[0..2]);
+        let mut units = 1_000_000;
+
+        for (code, value) in self.options(&body[8..]) {
+            if code == OPT_IF_TSRESOL && value.len() == 1 {
+                let exponent = u32::from(value[0] & 0x7F);
+                units = match value[0] & 0x80 {
+                    0 => 10u64.checked_pow(exponent),
+                    _ => 2u64.checked_pow(exponent),
+                }
+                .ok_or_else(|| invalid("bad timestamp resolution"))?;
+            }
+        }
+
+        Ok((linktype, units))
+    }
+
+    fn packet(&self, body: &[u8]) -> io::Result<Captured> {
+        if body.len() < 20 {
+            return Err(invalid("short packet block"));
+        }
+
+        let interface = self.u32(&body[0..4]) as usize;
+        let ticks = u64::from(self.u32(&body[4..8])) << 32
+            | u64::from(self.u32(&body[8..12]));
+        let captured = self.u32(&body[12..16]) as usize;
+
+        let end = 20 + captured;
+        let data = body
+            .get(20..end)
+            .ok_or_else(|| invalid("packet longer than block"))?;
+        let &(linktype, units) = self
+            .interfaces
+            .get(interface)
+            .ok_or_else(|| invalid("unknown interface"))?;
+
+        let options = body.get(end.next_multiple_of(4)..).unwrap_or(&[]);
+        let direction = self
+            .options(options)
+            .find(|(code, value)| *code == OPT_EPB_FLAGS && value.len() == 4)
+            .and_then(|(_, value)| match self.u32(value) & 0b11 {
+                1 => Some(Direction::Inbound),
+                2 => Some(Direction::Outbound),
+                _ => None,
+            });
+
+        Ok(Captured {
+            timestamp: timestamp(ticks, units),
+            direction,
+            linktype,
+            data: data.to_vec(),
+        })
+    }
+
+    /// Iterate over the options in `buf`, stopping at the end of options
+    /// or at a truncated option.
+    fn options<'a>(
+        &'a self,
+        mut buf: &'a [u8],
+    ) -> impl Iterator<Item = (u16, &'a [u8])> + 'a {
+        core::iter::from_fn(move || {
+            if buf.len() < 4 {
+                return None;
+            }
+
+            let code = self.u16(&buf[0..2]);
+            let len = self.u16(&buf[2..4]) as usize;
+            let value = buf.get(4..4 + len)?;
+            buf = buf.get((4 + len).next_multiple_of(4)..).unwrap_or(&[]);
+
+            (code != OPT_END).then_some((code, value))
+        })
+    }
+
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+
+        match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+
+        match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        }
+    }
+}
+
+fn timestamp(ticks: u64, units: u64) -> Duration {
+    let nanos = u128::from(ticks % units) * 1_000_000_000 / u128::from(units);
+
+    Duration::new(ticks / units, nanos as u32)
+}
+
+/// Transport layer contents of a captured IPv4 packet.
+#[derive(Debug)]
+pub(crate) struct Segment<'a> {
+    pub source: SocketAddrV4,
+    pub destination: SocketAddrV4,
+    pub transport: Transport<'a>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Transport<'a> {
+    /// UDP datagram payload.
+    Udp(&'a [u8]),
+    Tcp(Tcp<'a>),
+}
+
+/// TCP segment.
+#[derive(Debug)]
+pub(crate) struct Tcp<'a> {
+    pub sequence: u32,
+    pub syn: bool,
+    /// Whether the segment carries a FIN or RST, ending the stream.
+    pub end: bool,
+    pub payload: &'a [u8],
+}
+
+/// Parse the UDP or TCP contents of an unfragmented IPv4 packet.
+pub(crate) fn parse(linktype: u16, data: &[u8]) -> Option<Segment<'_>> {
+    let ip = ipv4(linktype, data)?;
+    let header_len = usize::from(ip.first()? & 0x0F) * 4;
+    if ip[0] >> 4 != 4 || header_len < IPV4_LEN || ip.len() < header_len {
+        return None;
+    }
+
+    // more fragments flag or a fragment offset
+    if be16(ip, 6)? & 0x3FFF != 0 {
+        return None;
+    }
+
+    // drop link layer padding
+    let total_len = usize::from(be16(ip, 2)?);
+    let ip = ip.get(..total_len)?;
+    if total_len < header_len {
+        return None;
+    }
+
+    let source = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+    let destination = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+    let body = &ip[header_len..];
+    let source = SocketAddrV4::new(source, be16(body, 0)?);
+    let destination = SocketAddrV4::new(destination, be16(body, 2)?);
+
+    let transport = match ip[9] {
+        IP_PROTOCOL_UDP => {
+            let len = usize::from(be16(body, 4)?);
+            Transport::Udp(body.get(UDP_LEN..len.max(UDP_LEN))?)
+        }
+        IP_PROTOCOL_TCP => {
+            let header_len = usize::from(*body.get(12)? >> 4) * 4;
+            let flags = *body.get(13)?;
+
+            Transport::Tcp(Tcp {
+                sequence: u32::from_be_bytes(body.get(4..8)?.try_into().ok()?),
+                syn: flags & TCP_SYN != 0,
+                end: flags & (TCP_FIN | TCP_RST) != 0,
+                payload: body.get(header_len.max(TCP_LEN)..)?,
+            })
+        }
+        _ => return None,
+    };
+
+    Some(Segment {
+        source,
+        destination,
+        transport,
+    })
+}
+
+/// The IPv4 packet carried by a link layer packet.
+fn ipv4(linktype: u16, data: &[u8]) -> Option<&[u8]> {
+    let (ethertype, offset) = match linktype {
+        LINKTYPE_ETHERNET => {
+            // skip any VLAN tags
+            let mut offset = ETHERNET_LEN - 2;
+            while matches!(be16(data, offset)?, ETHERTYPE_VLAN | ETHERTYPE_QINQ)
+            {
+                offset += 4;
+            }
+            (be16(data, offset)?, offset + 2)
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 => (ETHERTYPE_IPV4, 0),
+        LINKTYPE_LINUX_SLL => (be16(data, 14)?, 16),
+        LINKTYPE_LINUX_SLL2 => (be16(data, 0)?, 20),
+        _ => return None,
+    };
+
+    (ethertype == ETHERTYPE_IPV4).then(|| data.get(offset..))?
+}
+
+fn be16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *data.get(offset)?,
+        *data.get(offset + 1)?,
+    ]))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{datagram::Frame, Bitrate, BusNumber, BROADCAST, PORT};
+    use embedded_can::StandardId;
+
+    fn records() -> Vec<Record> {
+        let adapter = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 10), PORT);
+        let group = match BROADCAST {
+            core::net::IpAddr::V4(group) => SocketAddrV4::new(group, PORT),
+            _ => unreachable!(),
+        };
+
+        let id = StandardId::new(0x123).unwrap();
+        let frame = <Frame as embedded_can::Frame>::new(id, &[1, 2]).unwrap();
+
+        vec![
+            Record {
+                timestamp: Duration::new(1_700_000_000, 123_456_000),
+                direction: Some(Direction::Outbound),
+                source: adapter,
+                destination: group,
+                packet: Packet::new_frame(&BusNumber::default(), &frame)
+                    .unwrap(),
+            },
+            Record {
+                timestamp: Duration::new(1_700_000_001, 0),
+                direction: None,
+                source: adapter,
+                destination: group,
+                packet: Packet::new_heartbeat(
+                    &[2, 0, 0, 0, 0, 1],
+                    &BusNumber::default(),
+                    &Bitrate::Kbps500,
+                ),
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut writer = Writer::new(vec![]).unwrap();
+        for record in records() {
+            writer.write(&record).unwrap();
+        }
+        let capture = writer.into_inner();
+
+        let reader = Reader::new(&capture[..]).unwrap();
+        let read: Vec<_> = reader.collect::<io::Result<_>>().unwrap();
+        assert_eq!(read, records());
+    }
+
+    #[test]
+    fn encapsulation() {
+        let data = encapsulate(&records()[0]);
+        assert_eq!(data.len(), ETHERNET_LEN + IPV4_LEN + UDP_LEN + PACKET_LEN);
+
+        // multicast group MAC address, then the locally administered source
+        assert_eq!(data[0..6], [0x01, 0x00, 0x5E, 0x7F, 60, 60]);
+        assert_eq!(data[6..12], [0x02, 0x00, 192, 168, 1, 10]);
+
+        // a valid header sums to zero
+        assert_eq!(checksum(&data[ETHERNET_LEN..ETHERNET_LEN + IPV4_LEN]), 0);
+    }
+
+    #[test]
+    fn big_endian() {
+        // section header and an interface with nanosecond timestamps
+        let mut capture = vec![];
+        capture.extend(SECTION_HEADER.to_be_bytes());
+        capture.extend(28u32.to_be_bytes());
+        capture.extend(BYTE_ORDER_MAGIC.to_be_bytes());
+        capture.extend([0, 1, 0, 0]);
+        capture.extend([0xFF; 8]);
+        capture.extend(28u32.to_be_bytes());
+
+        capture.extend(INTERFACE_DESCRIPTION.to_be_bytes());
+        capture.extend(28u32.to_be_bytes());
+        capture.extend(LINKTYPE_ETHERNET.to_be_bytes());
+        capture.extend([0, 0, 0, 0, 0xFF, 0xFF]);
+        capture.extend(OPT_IF_TSRESOL.to_be_bytes());
+        capture.extend([0, 1, 9, 0, 0, 0]);
+        capture.extend(28u32.to_be_bytes());
+
+        let record = &records()[1];
+        let data = encapsulate(record);
+        let nanos = record.timestamp.as_nanos() as u64;
+        let len = 12 + 20 + data.len().next_multiple_of(4);
+        capture.extend(ENHANCED_PACKET.to_be_bytes());
+        capture.extend((len as u32).to_be_bytes());
+        capture.extend(0u32.to_be_bytes());
+        capture.extend(((nanos >> 32) as u32).to_be_bytes());
+        capture.extend((nanos as u32).to_be_bytes());
+        capture.extend((data.len() as u32).to_be_bytes());
+        capture.extend((data.len() as u32).to_be_bytes());
+        capture.extend(&data);
+        capture.resize(capture.len().next_multiple_of(4), 0);
+        capture.extend((len as u32).to_be_bytes());
+
+        let mut reader = Reader::new(&capture[..]).unwrap();
+        assert_eq!(reader.read().unwrap().as_ref(), Some(record));
+        assert!(reader.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_total_length() {
+        let mut data = encapsulate(&records()[0]).split_off(ETHERNET_LEN);
+        assert!(parse(LINKTYPE_RAW, &data).is_some());
+
+        // shorter than the header it claims to have
+        data[2..4].copy_from_slice(&4u16.to_be_bytes());
+        assert!(parse(LINKTYPE_RAW, &data).is_none());
+    }
+
+    #[test]
+    fn not_a_capture() {
+        assert!(Reader::new(&[0u8; 32][..]).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{BusNumber, Flags, PROTOCOL_VERSION};
+use crate::{Bitrate, BusNumber, Flags, PROTOCOL_VERSION};
+use core::{fmt, str::FromStr};
 use embedded_can::{ExtendedId, Id, StandardId};
 use zerocopy::{AsBytes, FromBytes, FromZeroes};
 
@@ -7,7 +8,7 @@ const HEADER_LEN: usize = 16;
 
 bitfield::bitfield! {
     /// Datagram header, used when receiving UDP data and sending TCP data.
-    #[derive(AsBytes, FromBytes, FromZeroes)]
+    #[derive(Clone, Copy, PartialEq, Eq, AsBytes, FromBytes, FromZeroes)]
     #[repr(transparent)]
     #[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
     pub struct HeaderBitfield(MSB0 [u8]);
@@ -23,6 +24,21 @@ impl Header {
     pub fn new() -> Self {
         HeaderBitfield([0; HEADER_LEN])
     }
+
+    /// Create a header for the given bus with the protocol version set.
+    pub fn with_bus_number(bus_number: &BusNumber) -> Self {
+        let mut header = Header::new();
+        header.set_version(PROTOCOL_VERSION);
+        header.set_bus_number(bus_number.0);
+        header.set_client_identifier(0);
+        header
+    }
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl embedded_can::Frame for Frame {
@@ -70,31 +86,37 @@ impl embedded_can::Frame for Frame {
     }
 
     fn is_extended(&self) -> bool {
-        Flags::from_bits(self.flags())
-            .unwrap()
-            .intersects(Flags::Extended)
+        Flags::from_bits_truncate(self.flags()).intersects(Flags::Extended)
     }
 
     fn is_remote_frame(&self) -> bool {
-        Flags::from_bits(self.flags())
-            .unwrap()
-            .intersects(Flags::Remote)
+        Flags::from_bits_truncate(self.flags()).intersects(Flags::Remote)
     }
 
     fn id(&self) -> Id {
+        // out of range identifiers are rejected when decoding a `Message`,
+        // mask here so that this never panics.
         if self.is_extended() {
-            Id::Extended(ExtendedId::new(self.id()).unwrap())
+            let id = self.id() & ExtendedId::MAX.as_raw();
+            Id::Extended(ExtendedId::new(id).unwrap())
         } else {
-            Id::Standard(StandardId::new(self.id() as u16).unwrap())
+            let id = self.id() as u16 & StandardId::MAX.as_raw();
+            Id::Standard(StandardId::new(id).unwrap())
         }
     }
+
     fn dlc(&self) -> usize {
         self.dlc() as usize
     }
 
     fn data(&self) -> &[u8] {
-        // todo: check if this has the right byte order
-        &self.0[22..]
+        if self.is_remote_frame() {
+            return &[];
+        }
+
+        // data bytes are sent in order, see the conformance tests
+        let len = (self.dlc() as usize).min(8);
+        &self.0[6..6 + len]
     }
 }
 
@@ -104,7 +126,7 @@ bitfield::bitfield! {
     /// Frame datagram only including the CAN frame section.
     ///
     /// Used for incomming frames on a TCP connection stream.
-    #[derive(AsBytes, FromBytes, FromZeroes)]
+    #[derive(Clone, Copy, PartialEq, Eq, AsBytes, FromBytes, FromZeroes)]
     #[repr(transparent)]
     #[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
     pub struct FrameBitfield(MSB0 [u8]);
@@ -125,21 +147,21 @@ impl Frame {
         FrameBitfield([0; FRAME_LEN])
     }
 
-    pub fn from_frame(frame: &impl embedded_can::Frame) -> Result<Self, ()> {
+    /// Convert any [`embedded_can::Frame`] returning `None` if the frame is
+    /// longer than 8 bytes.
+    ///
+    /// This returned `Result<Self, ()>` in 0.1.0, the error carried no
+    /// information so callers only need to match `None` instead of `Err(())`.
+    pub fn from_frame(frame: &impl embedded_can::Frame) -> Option<Self> {
         if frame.dlc() > 8 {
             // we only support standard frames of up to 8 bytes in length.
-            return Err(()); // todo: descriptive error.
+            return None;
         }
 
-        let mut data: u64 = 0;
-
-        for (n, &byte) in frame.data().iter().rev().enumerate() {
-            if n < frame.dlc() as usize {
-                data |= (byte as u64) << (n * 8);
-            } else {
-                break;
-            }
-        }
+        // data is stored from the first byte, the same as `Frame::new`.
+        let mut data = [0u8; 8];
+        let len = frame.data().len().min(frame.dlc());
+        data[..len].copy_from_slice(&frame.data()[..len]);
 
         let mut dg = Frame::new();
         dg.set_flags(Flags::from_frame(frame).bits());
@@ -148,16 +170,124 @@ impl Frame {
             Id::Extended(id) => id.as_raw(),
         });
         dg.set_dlc(frame.dlc() as u8);
-        dg.set_data(data);
+        dg.set_data(u64::from_be_bytes(data));
+
+        Some(dg)
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats the frame in SocketCAN `candump -L` notation, e.g. `123#DEADBEEF`,
+/// `1ABCDEF0#` or `123#R4`.
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match embedded_can::Frame::id(self) {
+            Id::Standard(id) => write!(f, "{:03X}#", id.as_raw())?,
+            Id::Extended(id) => write!(f, "{:08X}#", id.as_raw())?,
+        }
+
+        if embedded_can::Frame::is_remote_frame(self) {
+            match embedded_can::Frame::dlc(self) {
+                0 => f.write_str("R"),
+                dlc => write!(f, "R{dlc}"),
+            }
+        } else {
+            embedded_can::Frame::data(self)
+                .iter()
+                .try_for_each(|byte| write!(f, "{byte:02X}"))
+        }
+    }
+}
+
+/// Parses a frame in SocketCAN `candump -L` or `cansend` notation.
+///
+/// Standard identifiers are 3 hex digits and extended identifiers 8, data
+/// bytes may be separated by `.`.
+impl FromStr for Frame {
+    type Err = ParseFrameError;
 
-        Ok(dg)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, data) = s.split_once('#').ok_or(ParseFrameError::Separator)?;
+
+        if !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseFrameError::Identifier);
+        }
+        let raw = u32::from_str_radix(id, 16)
+            .map_err(|_| ParseFrameError::Identifier)?;
+        let id: Id = match id.len() {
+            3 => StandardId::new(raw as u16).map(Id::from),
+            8 => ExtendedId::new(raw).map(Id::from),
+            _ => None,
+        }
+        .ok_or(ParseFrameError::Identifier)?;
+
+        if let Some(dlc) = data.strip_prefix('R') {
+            let dlc = match dlc {
+                "" => 0,
+                dlc => dlc.parse().map_err(|_| ParseFrameError::Data)?,
+            };
+            return embedded_can::Frame::new_remote(id, dlc)
+                .ok_or(ParseFrameError::Length);
+        }
+
+        let hex = |b: u8| {
+            char::from(b)
+                .to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or(ParseFrameError::Data)
+        };
+
+        let mut bytes = [0u8; 8];
+        let mut len = 0;
+        let mut digits = data.bytes().filter(|&b| b != b'.');
+        while let Some(high) = digits.next() {
+            let low = digits.next().ok_or(ParseFrameError::Data)?;
+            let byte = bytes.get_mut(len).ok_or(ParseFrameError::Length)?;
+            *byte = hex(high)? << 4 | hex(low)?;
+            len += 1;
+        }
+
+        embedded_can::Frame::new(id, &bytes[..len])
+            .ok_or(ParseFrameError::Length)
+    }
+}
+
+/// Error parsing a [`Frame`] from text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum ParseFrameError {
+    /// There is no `#` between the identifier and the data.
+    Separator,
+    /// The identifier is not 3 or 8 hex digits, or is out of range.
+    Identifier,
+    /// The data is not whole hex bytes, or the remote length is not a number.
+    Data,
+    /// The frame is longer than 8 bytes.
+    Length,
+}
+
+impl fmt::Display for ParseFrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Separator => "missing '#' after the identifier",
+            Self::Identifier => "identifier must be 3 or 8 hex digits",
+            Self::Data => "data must be whole hex bytes",
+            Self::Length => "more than 8 data bytes",
+        })
     }
 }
 
+impl core::error::Error for ParseFrameError {}
+
 /// Complete datagram packet.
 ///
 /// Used when receiving UDP frames and sending frames for both UDP and TCP.
-#[derive(Debug, FromBytes, AsBytes, FromZeroes)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, AsBytes, FromZeroes)]
 #[repr(C)]
 #[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct Packet {
@@ -166,16 +296,37 @@ pub struct Packet {
 }
 
 impl Packet {
+    /// Create a packet carrying a CAN frame on the given bus.
+    ///
+    /// Returns `None` if the frame is longer than 8 bytes.
+    pub fn new_frame(
+        bus_number: &BusNumber,
+        frame: &impl embedded_can::Frame,
+    ) -> Option<Self> {
+        Some(Packet {
+            header: Header::with_bus_number(bus_number),
+            frame: Frame::from_frame(frame)?,
+        })
+    }
+
+    /// Create the header sent by both ends when a TCP stream is opened.
+    pub fn new_stream_header(bus_number: &BusNumber) -> Self {
+        Packet {
+            header: Header::with_bus_number(bus_number),
+            frame: Frame::new(),
+        }
+    }
+
     pub fn new_heartbeat(
         mac_addr: &[u8; 6],
         bus_number: &BusNumber,
-        data_rate: &u16,
+        data_rate: &Bitrate,
     ) -> Self {
         let flags = Flags::Heartbeat;
 
         let mut data = [0u8; 8];
         // bitrate
-        data[0..2].copy_from_slice(&data_rate.to_be_bytes());
+        data[0..2].copy_from_slice(&u16::from(*data_rate).to_be_bytes());
         data[2..8].copy_from_slice(mac_addr);
 
         let mut packet = Packet {
@@ -208,19 +359,149 @@ impl Packet {
             )
         }
     }
+
+    /// Decode the message carried by this packet.
+    pub fn message(&self) -> Result<Message, DecodeError> {
+        if self.header.version() != PROTOCOL_VERSION {
+            return Err(DecodeError::Version);
+        }
+
+        let bus_number = BusNumber::try_from(self.header.bus_number())
+            .map_err(|_| DecodeError::BusNumber)?;
+
+        Message::from_frame(self.frame, bus_number)
+    }
+}
+
+/// Errors that can occur while decoding a datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum DecodeError {
+    /// The datagram is not the expected length.
+    Length,
+    /// The header does not carry [`PROTOCOL_VERSION`].
+    Version,
+    /// The header bus number is out of range.
+    BusNumber,
+    /// The frame has an invalid identifier or data length.
+    Frame,
+}
+
+/// Heartbeat periodically sent by each adapter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Heartbeat {
+    pub bus_number: BusNumber,
+    pub data_rate: Bitrate,
+    pub mac_addr: [u8; 6],
+}
+
+impl Heartbeat {
+    /// Check a heartbeat received from another adapter against this one.
+    ///
+    /// Heartbeats with the same MAC address are assumed to be our own.
+    pub fn conflict(&self, other: &Heartbeat) -> Option<Conflict> {
+        if other.mac_addr == self.mac_addr
+            || other.bus_number != self.bus_number
+        {
+            return None;
+        }
+
+        if other.data_rate != self.data_rate {
+            Some(Conflict::DataRate {
+                mac_addr: other.mac_addr,
+                data_rate: other.data_rate,
+            })
+        } else {
+            Some(Conflict::BusNumber {
+                mac_addr: other.mac_addr,
+            })
+        }
+    }
+}
+
+/// Another adapter is configured in a way that interferes with this one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Conflict {
+    /// Another adapter uses the same bus number.
+    BusNumber { mac_addr: [u8; 6] },
+    /// Another adapter uses the same bus number with a different data rate.
+    DataRate {
+        mac_addr: [u8; 6],
+        data_rate: Bitrate,
+    },
+}
+
+/// A decoded datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Message {
+    /// Adapter heartbeat.
+    Heartbeat(Heartbeat),
+    /// Adapter settings, passed through uninterpreted.
+    Settings(Frame),
+    /// CAN frame.
+    Frame(Frame),
+}
+
+impl Message {
+    /// Decode a complete UDP datagram.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Packet::read_from(bytes)
+            .ok_or(DecodeError::Length)?
+            .message()
+    }
+
+    /// Decode a frame received on the given bus.
+    pub fn from_frame(
+        frame: Frame,
+        bus_number: BusNumber,
+    ) -> Result<Self, DecodeError> {
+        let flags = Flags::from_bits_truncate(frame.flags());
+
+        if flags.contains(Flags::Heartbeat) {
+            let data = frame.data().to_be_bytes();
+            let mut mac_addr = [0u8; 6];
+            mac_addr.copy_from_slice(&data[2..8]);
+
+            return Ok(Message::Heartbeat(Heartbeat {
+                bus_number,
+                data_rate: u16::from_be_bytes([data[0], data[1]]).into(),
+                mac_addr,
+            }));
+        }
+
+        if flags.contains(Flags::Settings) {
+            return Ok(Message::Settings(frame));
+        }
+
+        let max_id = if flags.contains(Flags::Extended) {
+            ExtendedId::MAX.as_raw()
+        } else {
+            StandardId::MAX.as_raw() as u32
+        };
+
+        if frame.id() > max_id || frame.dlc() > 8 {
+            return Err(DecodeError::Frame);
+        }
+
+        Ok(Message::Frame(frame))
+    }
 }
 
 /// Filter setting datagram length.
 pub const FILTER_LEN: usize = 24;
 
 bitfield::bitfield! {
-    /// Datagram use for filt
+    /// Filter setting datagram, selecting the frames an adapter forwards.
     #[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
     pub struct FilterBitfield(MSB0 [u8]);
     impl Debug;
     pub u32, fwd_identifier, set_fwd_identifier: 31, 0;
     pub u32, fwd_range, set_fwd_range: 63, 32;
-    pub u8, bus_number, set_bus_bumber: 71, 64;
+    // as originally laid out, not yet confirmed against an adapter
+    pub u8, bus_number, set_bus_number: 71, 64;
     pub u64, version_number, set_version_number: 123, 72;
     pub u64, client_identifier, set_client_identifier: 187, 132;
 }
@@ -233,6 +514,12 @@ impl Filter {
     }
 }
 
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,4 +540,103 @@ mod tests {
     fn packet_type_length() {
         assert_eq!(size_of::<Packet>(), 30)
     }
+
+    #[test]
+    fn frame_data() {
+        let id = StandardId::new(0x123).unwrap();
+        let frame =
+            <Frame as embedded_can::Frame>::new(id, &[0xAA, 0xBB]).unwrap();
+        assert_eq!(embedded_can::Frame::data(&frame), &[0xAA, 0xBB]);
+
+        let copy = Frame::from_frame(&frame).unwrap();
+        assert_eq!(copy, frame);
+    }
+
+    #[test]
+    fn heartbeat_round_trip() {
+        let mac_addr = [0x02, 0x00, 0x00, 0x12, 0x34, 0x56];
+        let bus_number = BusNumber::try_from(3).unwrap();
+        let packet =
+            Packet::new_heartbeat(&mac_addr, &bus_number, &Bitrate::Kbps500);
+
+        assert_eq!(
+            Message::decode(packet.as_bytes()),
+            Ok(Message::Heartbeat(Heartbeat {
+                bus_number,
+                data_rate: Bitrate::Kbps500,
+                mac_addr,
+            }))
+        );
+    }
+
+    #[test]
+    fn heartbeat_conflict() {
+        let own = Heartbeat {
+            bus_number: BusNumber::default(),
+            data_rate: Bitrate::Kbps500,
+            mac_addr: [0x02, 0, 0, 0, 0, 1],
+        };
+        let mut other = Heartbeat {
+            mac_addr: [0x02, 0, 0, 0, 0, 2],
+            ..own
+        };
+
+        assert_eq!(own.conflict(&own), None);
+        assert_eq!(
+            own.conflict(&other),
+            Some(Conflict::BusNumber {
+                mac_addr: other.mac_addr
+            })
+        );
+
+        other.data_rate = Bitrate::Kbps250;
+        assert_eq!(
+            own.conflict(&other),
+            Some(Conflict::DataRate {
+                mac_addr: other.mac_addr,
+                data_rate: Bitrate::Kbps250
+            })
+        );
+
+        other.bus_number = BusNumber::try_from(1).unwrap();
+        assert_eq!(own.conflict(&other), None);
+    }
+
+    #[test]
+    fn decode_rejects_invalid() {
+        let bus_number = BusNumber::default();
+        let mut packet = Packet::new_stream_header(&bus_number);
+        packet.frame.set_id(0x800);
+        assert_eq!(packet.message(), Err(DecodeError::Frame));
+
+        packet.header.set_version(0);
+        assert_eq!(packet.message(), Err(DecodeError::Version));
+
+        assert_eq!(Message::decode(&[0; 29]), Err(DecodeError::Length));
+    }
+
+    #[test]
+    fn candump_notation() {
+        for s in ["123#DEADBEEF", "1ABCDEF0#", "7FF#R", "00000010#R8"] {
+            assert_eq!(s.parse::<Frame>().unwrap().to_string(), s);
+        }
+
+        let frame: Frame = "123#DE.AD.be.ef".parse().unwrap();
+        assert_eq!(embedded_can::Frame::data(&frame), [0xDE, 0xAD, 0xBE, 0xEF]);
+
+        let frame: Frame = "7FF#R2".parse().unwrap();
+        assert!(embedded_can::Frame::is_remote_frame(&frame));
+        assert_eq!(embedded_can::Frame::dlc(&frame), 2);
+
+        let err = |s: &str| s.parse::<Frame>().unwrap_err();
+        assert_eq!(err("123"), ParseFrameError::Separator);
+        assert_eq!(err("800#00"), ParseFrameError::Identifier);
+        assert_eq!(err("12#00"), ParseFrameError::Identifier);
+        assert_eq!(err("+12#00"), ParseFrameError::Identifier);
+        assert_eq!(err("123#000"), ParseFrameError::Data);
+        assert_eq!(err("123#0G"), ParseFrameError::Data);
+        assert_eq!(err("123##100"), ParseFrameError::Data);
+        assert_eq!(err("123#R9"), ParseFrameError::Length);
+        assert_eq!(err("123#000000000000000000"), ParseFrameError::Length);
+    }
 }
@@ -0,0 +1,202 @@
+//! Adapter discovery from heartbeats.
+//!
+//! Time is passed in as a [`Duration`] since an arbitrary, monotonic epoch so
+//! that the table can be driven by any clock.
+
+use crate::datagram::Heartbeat;
+use crate::{Bitrate, BusNumber, HEARTBEAT_INTERVAL};
+use core::net::IpAddr;
+use core::time::Duration;
+
+/// An adapter seen on the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Adapter {
+    pub mac_addr: [u8; 6],
+    pub addr: IpAddr,
+    pub bus_number: BusNumber,
+    pub data_rate: Bitrate,
+    /// Time the last heartbeat was received.
+    pub last_seen: Duration,
+}
+
+/// Change in the set of known adapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Event {
+    /// A heartbeat was received from a new adapter.
+    Appeared(Adapter),
+    /// An adapter missed too many heartbeats and was removed.
+    Disappeared(Adapter),
+}
+
+/// Fixed capacity table of adapters, populated from heartbeats.
+#[derive(Debug)]
+pub struct Discovery<const N: usize> {
+    adapters: [Option<Adapter>; N],
+    interval: Duration,
+    max_missed: u32,
+}
+
+impl<const N: usize> Discovery<N> {
+    /// Create an empty table, expiring adapters after `max_missed` heartbeat
+    /// intervals of length `interval` without a heartbeat.
+    pub const fn new(interval: Duration, max_missed: u32) -> Self {
+        Self {
+            adapters: [None; N],
+            interval,
+            max_missed,
+        }
+    }
+
+    /// Interval adapters are expected to send heartbeats at.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Set the interval adapters are expected to send heartbeats at.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Number of missed heartbeats before an adapter is removed.
+    pub fn max_missed(&self) -> u32 {
+        self.max_missed
+    }
+
+    /// Set the number of missed heartbeats before an adapter is removed.
+    pub fn set_max_missed(&mut self, max_missed: u32) {
+        self.max_missed = max_missed;
+    }
+
+    /// Record a heartbeat received from `addr`.
+    ///
+    /// Returns [`Event::Appeared`] the first time an adapter is seen. Heartbeats
+    /// from new adapters are ignored while the table is full.
+    pub fn ingest(
+        &mut self,
+        heartbeat: &Heartbeat,
+        addr: IpAddr,
+        now: Duration,
+    ) -> Option<Event> {
+        let adapter = Adapter {
+            mac_addr: heartbeat.mac_addr,
+            addr,
+            bus_number: heartbeat.bus_number,
+            data_rate: heartbeat.data_rate,
+            last_seen: now,
+        };
+
+        if let Some(known) = self
+            .adapters
+            .iter_mut()
+            .flatten()
+            .find(|known| known.mac_addr == heartbeat.mac_addr)
+        {
+            *known = adapter;
+            return None;
+        }
+
+        let slot = self.adapters.iter_mut().find(|slot| slot.is_none())?;
+        *slot = Some(adapter);
+
+        Some(Event::Appeared(adapter))
+    }
+
+    /// Remove an adapter that has missed too many heartbeats.
+    ///
+    /// Returns one [`Event::Disappeared`] per call, call until `None` is
+    /// returned.
+    pub fn poll(&mut self, now: Duration) -> Option<Event> {
+        let timeout = self.interval * self.max_missed;
+
+        let slot = self.adapters.iter_mut().find(|slot| match slot {
+            Some(adapter) => now.saturating_sub(adapter.last_seen) > timeout,
+            None => false,
+        })?;
+
+        slot.take().map(Event::Disappeared)
+    }
+
+    /// Find an adapter by MAC address.
+    pub fn get(&self, mac_addr: &[u8; 6]) -> Option<&Adapter> {
+        self.iter().find(|adapter| &adapter.mac_addr == mac_addr)
+    }
+
+    /// Iterate over the known adapters.
+    pub fn iter(&self) -> impl Iterator<Item = &Adapter> {
+        self.adapters.iter().flatten()
+    }
+
+    /// Number of known adapters.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<const N: usize> Default for Discovery<N> {
+    /// Expire adapters after 3 missed heartbeats at the default
+    /// [`HEARTBEAT_INTERVAL`].
+    fn default() -> Self {
+        Self::new(HEARTBEAT_INTERVAL, 3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::net::Ipv4Addr;
+
+    fn heartbeat(mac: u8) -> Heartbeat {
+        Heartbeat {
+            bus_number: BusNumber::default(),
+            data_rate: Bitrate::Kbps500,
+            mac_addr: [0x02, 0, 0, 0, 0, mac],
+        }
+    }
+
+    const ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 10));
+
+    #[test]
+    fn appear_and_expire() {
+        let mut discovery = Discovery::<2>::new(HEARTBEAT_INTERVAL, 2);
+        let t = Duration::from_millis;
+
+        assert!(matches!(
+            discovery.ingest(&heartbeat(1), ADDR, t(0)),
+            Some(Event::Appeared(_))
+        ));
+        assert_eq!(discovery.ingest(&heartbeat(1), ADDR, t(1000)), None);
+        assert!(discovery.ingest(&heartbeat(2), ADDR, t(1500)).is_some());
+        assert_eq!(discovery.len(), 2);
+
+        // table is full
+        assert_eq!(discovery.ingest(&heartbeat(3), ADDR, t(1500)), None);
+
+        assert_eq!(discovery.poll(t(3000)), None);
+        match discovery.poll(t(3001)) {
+            Some(Event::Disappeared(adapter)) => {
+                assert_eq!(adapter.mac_addr, heartbeat(1).mac_addr);
+                assert_eq!(adapter.last_seen, t(1000));
+            }
+            event => panic!("unexpected {event:?}"),
+        }
+        assert_eq!(discovery.poll(t(3001)), None);
+        assert!(discovery.get(&heartbeat(2).mac_addr).is_some());
+    }
+
+    #[test]
+    fn interval() {
+        let mut discovery = Discovery::<1>::new(Duration::from_secs(5), 2);
+        let t = Duration::from_millis;
+
+        discovery.ingest(&heartbeat(1), ADDR, t(0));
+        assert_eq!(discovery.poll(t(10_000)), None);
+        assert!(discovery.poll(t(10_001)).is_some());
+    }
+}
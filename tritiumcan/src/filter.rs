@@ -0,0 +1,192 @@
+//! Software acceptance filters.
+//!
+//! Filters decide which frames cross the network, so that a constrained link
+//! only carries the identifiers the host is interested in.
+
+use embedded_can::{ExtendedId, Frame, Id, StandardId};
+
+/// A single acceptance rule, matching either standard or extended frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Rule {
+    /// Accept frames where `frame_id & mask == id & mask`.
+    Mask { extended: bool, id: u32, mask: u32 },
+    /// Accept frames with an identifier in `start..=end`.
+    Range {
+        extended: bool,
+        start: u32,
+        end: u32,
+    },
+}
+
+impl Rule {
+    /// Match standard frames against `id` for the bits set in `mask`.
+    pub fn standard_mask(id: StandardId, mask: u16) -> Self {
+        Rule::Mask {
+            extended: false,
+            id: id.as_raw().into(),
+            mask: mask.into(),
+        }
+    }
+
+    /// Match extended frames against `id` for the bits set in `mask`.
+    pub fn extended_mask(id: ExtendedId, mask: u32) -> Self {
+        Rule::Mask {
+            extended: true,
+            id: id.as_raw(),
+            mask,
+        }
+    }
+
+    /// Match standard frames with an identifier in `start..=end`.
+    pub fn standard_range(start: StandardId, end: StandardId) -> Self {
+        Rule::Range {
+            extended: false,
+            start: start.as_raw().into(),
+            end: end.as_raw().into(),
+        }
+    }
+
+    /// Match extended frames with an identifier in `start..=end`.
+    pub fn extended_range(start: ExtendedId, end: ExtendedId) -> Self {
+        Rule::Range {
+            extended: true,
+            start: start.as_raw(),
+            end: end.as_raw(),
+        }
+    }
+
+    /// Whether the identifier is accepted by this rule.
+    pub fn matches(&self, id: Id) -> bool {
+        let (is_extended, raw) = match id {
+            Id::Standard(id) => (false, id.as_raw().into()),
+            Id::Extended(id) => (true, id.as_raw()),
+        };
+
+        match *self {
+            Rule::Mask { extended, id, mask } => {
+                extended == is_extended && raw & mask == id & mask
+            }
+            Rule::Range {
+                extended,
+                start,
+                end,
+            } => extended == is_extended && (start..=end).contains(&raw),
+        }
+    }
+}
+
+/// Fixed capacity list of acceptance rules.
+///
+/// A frame is accepted if it matches any rule, an empty list accepts every
+/// frame.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Filters<const N: usize> {
+    rules: [Option<Rule>; N],
+}
+
+impl<const N: usize> Filters<N> {
+    /// Create an empty list, accepting every frame.
+    pub const fn new() -> Self {
+        Self { rules: [None; N] }
+    }
+
+    /// Add a rule, returning `false` if the list is full.
+    pub fn add(&mut self, rule: Rule) -> bool {
+        match self.rules.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(rule);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove all rules, accepting every frame.
+    pub fn clear(&mut self) {
+        self.rules = [None; N];
+    }
+
+    /// Configured rules.
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Whether the frame is accepted.
+    pub fn matches(&self, frame: &impl Frame) -> bool {
+        self.is_empty() || self.iter().any(|rule| rule.matches(frame.id()))
+    }
+}
+
+impl<const N: usize> Default for Filters<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::datagram::Frame as Datagram;
+
+    fn frame(id: impl Into<Id>) -> Datagram {
+        <Datagram as Frame>::new(id, &[]).unwrap()
+    }
+
+    fn standard(id: u16) -> StandardId {
+        StandardId::new(id).unwrap()
+    }
+
+    fn extended(id: u32) -> ExtendedId {
+        ExtendedId::new(id).unwrap()
+    }
+
+    #[test]
+    fn mask() {
+        let rule = Rule::standard_mask(standard(0x100), 0x7F0);
+        assert!(rule.matches(standard(0x10A).into()));
+        assert!(!rule.matches(standard(0x20A).into()));
+        assert!(!rule.matches(extended(0x10A).into()));
+
+        let rule = Rule::extended_mask(extended(0x1800_0000), 0x1F00_0000);
+        assert!(rule.matches(extended(0x18AB_CDEF).into()));
+        assert!(!rule.matches(extended(0x10AB_CDEF).into()));
+    }
+
+    #[test]
+    fn range() {
+        let rule = Rule::standard_range(standard(0x400), standard(0x4FF));
+        assert!(rule.matches(standard(0x400).into()));
+        assert!(rule.matches(standard(0x4FF).into()));
+        assert!(!rule.matches(standard(0x500).into()));
+        assert!(!rule.matches(extended(0x400).into()));
+
+        let rule = Rule::extended_range(extended(0x400), extended(0x4FF));
+        assert!(rule.matches(extended(0x450).into()));
+        assert!(!rule.matches(standard(0x450).into()));
+    }
+
+    #[test]
+    fn filters() {
+        let mut filters = Filters::<2>::new();
+        assert!(filters.matches(&frame(standard(0x123))));
+
+        assert!(filters.add(Rule::standard_mask(standard(0x123), 0x7FF)));
+        assert!(filters.add(Rule::extended_range(extended(0), extended(9))));
+        assert!(!filters.add(Rule::standard_mask(standard(0), 0)));
+
+        assert!(filters.matches(&frame(standard(0x123))));
+        assert!(filters.matches(&frame(extended(5))));
+        assert!(!filters.matches(&frame(standard(0x124))));
+
+        filters.clear();
+        assert!(filters.is_empty());
+        assert!(filters.matches(&frame(standard(0x124))));
+    }
+}
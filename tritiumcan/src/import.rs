@@ -0,0 +1,465 @@
+//! Import of Tritium traffic from pcap and pcapng captures.
+//!
+//! UDP datagrams and TCP streams to or from [`PORT`] are decoded for offline
+//! analysis. TCP segments are reassembled in sequence order, and streams are
+//! only followed from their SYN as frames can't be aligned without the stream
+//! header. Requires the `std` feature.
+
+use crate::capture::{parse, Source, Tcp, Transport};
+use crate::datagram::{DecodeError, Message, Packet, FRAME_LEN};
+use crate::stream::{Decoder, Item};
+use crate::{BusNumber, PORT};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read},
+    net::SocketAddrV4,
+    time::Duration,
+};
+use zerocopy::FromBytes;
+
+/// Number of out of order segments held per stream, beyond which the missing
+/// data is taken as lost.
+const MAX_AHEAD: usize = 32;
+
+/// A message imported from a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Imported {
+    /// Time since the Unix epoch of the packet completing the message.
+    pub timestamp: Duration,
+    pub source: SocketAddrV4,
+    pub destination: SocketAddrV4,
+    /// Bus number of the datagram or stream header, if it could be read.
+    pub bus_number: Option<BusNumber>,
+    pub message: Result<Message, DecodeError>,
+}
+
+/// Capture importer.
+///
+/// Yields the messages sent over UDP and TCP in capture order. A bad stream
+/// header is yielded as an error and the rest of that stream is skipped.
+#[derive(Debug)]
+pub struct Import<R> {
+    source: Source<R>,
+    /// Streams being followed, by source and destination.
+    streams: HashMap<(SocketAddrV4, SocketAddrV4), Stream>,
+    /// Decoded messages not yet returned.
+    pending: VecDeque<Imported>,
+}
+
+impl<R: Read> Import<R> {
+    /// Open a capture, reading its file or section header.
+    pub fn new(inner: R) -> io::Result<Self> {
+        Ok(Self {
+            source: Source::new(inner)?,
+            streams: HashMap::new(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Read the next message, `None` at the end of the capture.
+    pub fn read(&mut self) -> io::Result<Option<Imported>> {
+        loop {
+            if let Some(imported) = self.pending.pop_front() {
+                return Ok(Some(imported));
+            }
+
+            let Some(captured) = self.source.read()? else {
+                return Ok(None);
+            };
+            let Some(segment) = parse(captured.linktype, &captured.data) else {
+                continue;
+            };
+
+            let (source, destination) = (segment.source, segment.destination);
+            if source.port() != PORT && destination.port() != PORT {
+                continue;
+            }
+
+            let mut push = |bus_number, message| {
+                self.pending.push_back(Imported {
+                    timestamp: captured.timestamp,
+                    source,
+                    destination,
+                    bus_number,
+                    message,
+                })
+            };
+
+            match segment.transport {
+                Transport::Udp(payload) => {
+                    let bus_number = Packet::read_from(payload).and_then(|p| {
+                        BusNumber::try_from(p.header.bus_number()).ok()
+                    });
+                    push(bus_number, Message::decode(payload));
+                }
+                Transport::Tcp(tcp) => {
+                    let key = (source, destination);
+                    if tcp.syn {
+                        let next = tcp.sequence.wrapping_add(1);
+                        self.streams.insert(key, Stream::new(next));
+                    }
+
+                    if let Some(stream) = self.streams.get_mut(&key) {
+                        stream.push(&tcp);
+                        stream.decode(push);
+
+                        if tcp.end {
+                            self.streams.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Import<R> {
+    type Item = io::Result<Imported>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// One direction of a TCP connection.
+#[derive(Debug)]
+struct Stream {
+    /// Sequence number of the next byte expected.
+    next: u32,
+    /// Bytes received in order and not yet decoded.
+    buf: Vec<u8>,
+    /// Segments received ahead of `next`.
+    ahead: Vec<(u32, Vec<u8>)>,
+    /// Bytes still to be dropped to realign with a frame after lost data.
+    discard: usize,
+    decoder: Decoder,
+    /// Set after a bad stream header or lost data before it.
+    failed: bool,
+}
+
+impl Stream {
+    fn new(next: u32) -> Self {
+        Self {
+            next,
+            buf: vec![],
+            ahead: vec![],
+            discard: 0,
+            decoder: Decoder::new(),
+            failed: false,
+        }
+    }
+
+    fn push(&mut self, tcp: &Tcp) {
+        if tcp.payload.is_empty() || self.failed {
+            return;
+        }
+
+        // data sent along with a SYN follows its sequence number
+        let sequence = tcp.sequence.wrapping_add(u32::from(tcp.syn));
+        self.ahead.push((sequence, tcp.payload.to_vec()));
+        self.pull();
+
+        if self.ahead.len() > MAX_AHEAD {
+            self.skip();
+            self.pull();
+        }
+    }
+
+    /// Append the held segments that have come into order.
+    fn pull(&mut self) {
+        while let Some(i) = self
+            .ahead
+            .iter()
+            .position(|(sequence, _)| !is_after(*sequence, self.next))
+        {
+            let (sequence, payload) = self.ahead.swap_remove(i);
+
+            // skip data already received
+            let received = self.next.wrapping_sub(sequence) as usize;
+            let Some(payload) = payload.get(received..) else {
+                continue;
+            };
+            self.next = self.next.wrapping_add(payload.len() as u32);
+
+            let discard = self.discard.min(payload.len());
+            self.discard -= discard;
+            self.buf.extend(&payload[discard..]);
+        }
+    }
+
+    /// Give up on missing data, resuming from the earliest held segment.
+    fn skip(&mut self) {
+        let Some(&(sequence, _)) = self
+            .ahead
+            .iter()
+            .min_by_key(|(sequence, _)| sequence.wrapping_sub(self.next))
+        else {
+            return;
+        };
+
+        // frames are only aligned after the stream header
+        if self.decoder.bus_number().is_none() {
+            self.fail();
+            return;
+        }
+
+        let lost = sequence.wrapping_sub(self.next) as usize;
+        let into_frame = (self.buf.len() + lost) % FRAME_LEN;
+        self.discard = (FRAME_LEN - into_frame) % FRAME_LEN;
+        self.buf.clear();
+        self.next = sequence;
+    }
+
+    fn decode(
+        &mut self,
+        mut f: impl FnMut(Option<BusNumber>, Result<Message, DecodeError>),
+    ) {
+        let mut consumed = 0;
+
+        while !self.failed {
+            let Some((len, item)) = self.decoder.decode(&self.buf[consumed..])
+            else {
+                break;
+            };
+            consumed += len;
+
+            match item {
+                Ok(Item::Header(_)) => {}
+                Ok(Item::Message(message)) => {
+                    f(self.decoder.bus_number(), Ok(message))
+                }
+                Err(err) => {
+                    if self.decoder.bus_number().is_none() {
+                        self.fail();
+                    }
+                    f(self.decoder.bus_number(), Err(err));
+                }
+            }
+        }
+
+        if !self.failed {
+            self.buf.drain(..consumed);
+        }
+    }
+
+    fn fail(&mut self) {
+        self.failed = true;
+        self.buf.clear();
+        self.ahead.clear();
+    }
+}
+
+/// Whether sequence number `a` comes after `b`, allowing for wrap around.
+fn is_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::datagram::Frame;
+    use crate::stream::HEADER_LEN;
+    use embedded_can::StandardId;
+    use std::net::Ipv4Addr;
+    use zerocopy::AsBytes;
+
+    const ADAPTER: SocketAddrV4 =
+        SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 10), PORT);
+    const CLIENT: SocketAddrV4 =
+        SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 20), 50000);
+
+    const FIN: u8 = 0x01;
+    const SYN: u8 = 0x02;
+
+    fn bus() -> BusNumber {
+        BusNumber::try_from(3).unwrap()
+    }
+
+    fn frame(id: u16) -> Frame {
+        let id = StandardId::new(id).unwrap();
+        <Frame as embedded_can::Frame>::new(id, &[id.as_raw() as u8]).unwrap()
+    }
+
+    /// Ethernet frame carrying an IPv4 packet, padded to the minimum length.
+    fn ethernet(
+        source: SocketAddrV4,
+        destination: SocketAddrV4,
+        protocol: u8,
+        transport: &[u8],
+    ) -> Vec<u8> {
+        let mut data = vec![0; 12];
+        data.extend([0x08, 0x00, 0x45, 0]);
+        data.extend(((20 + transport.len()) as u16).to_be_bytes());
+        data.extend([0, 0, 0x40, 0, 64, protocol, 0, 0]);
+        data.extend(source.ip().octets());
+        data.extend(destination.ip().octets());
+        data.extend(transport);
+        data.resize(data.len().max(60), 0);
+        data
+    }
+
+    fn udp(from: SocketAddrV4, to: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+        let mut udp = vec![];
+        udp.extend(from.port().to_be_bytes());
+        udp.extend(to.port().to_be_bytes());
+        udp.extend(((8 + payload.len()) as u16).to_be_bytes());
+        udp.extend([0, 0]);
+        udp.extend(payload);
+        ethernet(from, to, 17, &udp)
+    }
+
+    fn tcp(
+        from: SocketAddrV4,
+        to: SocketAddrV4,
+        sequence: u32,
+        flags: u8,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut tcp = vec![];
+        tcp.extend(from.port().to_be_bytes());
+        tcp.extend(to.port().to_be_bytes());
+        tcp.extend(sequence.to_be_bytes());
+        tcp.extend([0, 0, 0, 0, 0x50, flags, 0xFF, 0xFF, 0, 0, 0, 0]);
+        tcp.extend(payload);
+        ethernet(from, to, 6, &tcp)
+    }
+
+    /// Little endian pcap capture of Ethernet packets, one second apart.
+    fn pcap(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut capture = vec![];
+        capture.extend(0xA1B2_C3D4u32.to_le_bytes());
+        capture.extend([2, 0, 4, 0]);
+        capture.extend([0; 8]);
+        capture.extend(65535u32.to_le_bytes());
+        capture.extend(1u32.to_le_bytes());
+
+        for (i, data) in packets.iter().enumerate() {
+            capture.extend((1_700_000_000 + i as u32).to_le_bytes());
+            capture.extend(250_000u32.to_le_bytes());
+            capture.extend((data.len() as u32).to_le_bytes());
+            capture.extend((data.len() as u32).to_le_bytes());
+            capture.extend(data);
+        }
+
+        capture
+    }
+
+    fn import(packets: &[Vec<u8>]) -> Vec<Imported> {
+        let capture = pcap(packets);
+        let import = Import::new(&capture[..]).unwrap();
+        import.collect::<io::Result<_>>().unwrap()
+    }
+
+    fn messages(imported: &[Imported]) -> Vec<Result<Message, DecodeError>> {
+        imported.iter().map(|imported| imported.message).collect()
+    }
+
+    #[test]
+    fn udp_datagrams() {
+        let packet = Packet::new_frame(&bus(), &frame(0x123)).unwrap();
+        let other = SocketAddrV4::new(*CLIENT.ip(), 53);
+
+        let imported = import(&[
+            udp(ADAPTER, CLIENT, packet.as_bytes()),
+            udp(CLIENT, other, packet.as_bytes()),
+            udp(ADAPTER, CLIENT, &packet.as_bytes()[..20]),
+        ]);
+
+        assert_eq!(
+            imported[0],
+            Imported {
+                timestamp: Duration::new(1_700_000_000, 250_000_000),
+                source: ADAPTER,
+                destination: CLIENT,
+                bus_number: Some(bus()),
+                message: Ok(Message::Frame(frame(0x123))),
+            }
+        );
+        assert_eq!(imported[1].bus_number, None);
+        assert_eq!(imported[1].message, Err(DecodeError::Length));
+        assert_eq!(imported.len(), 2);
+    }
+
+    #[test]
+    fn tcp_reassembly() {
+        let header = Packet::new_stream_header(&bus());
+        let mut stream = header.as_bytes().to_vec();
+        for id in 1..=3 {
+            stream.extend(frame(id).as_bytes());
+        }
+        let seq = |offset: usize| 1001 + offset as u32;
+
+        let imported = import(&[
+            tcp(CLIENT, ADAPTER, 1000, SYN, &[]),
+            // the rest of the header and the first frame arrive first
+            tcp(CLIENT, ADAPTER, seq(20), 0, &stream[20..HEADER_LEN + 14]),
+            tcp(CLIENT, ADAPTER, seq(0), 0, &stream[..20]),
+            // a retransmission overlapping new data
+            tcp(CLIENT, ADAPTER, seq(30), 0, &stream[30..HEADER_LEN + 28]),
+            tcp(CLIENT, ADAPTER, seq(58), FIN, &stream[58..]),
+            // no longer followed
+            tcp(CLIENT, ADAPTER, seq(72), 0, frame(4).as_bytes()),
+        ]);
+
+        assert_eq!(
+            messages(&imported),
+            (1..=3)
+                .map(|id| Ok(Message::Frame(frame(id))))
+                .collect::<Vec<_>>()
+        );
+        assert!(imported
+            .iter()
+            .all(|imported| imported.bus_number == Some(bus())
+                && imported.source == CLIENT));
+        assert_eq!(imported[0].timestamp.as_secs(), 1_700_000_002);
+    }
+
+    #[test]
+    fn tcp_mid_stream() {
+        let imported =
+            import(&[tcp(ADAPTER, CLIENT, 1, 0, frame(1).as_bytes())]);
+        assert!(imported.is_empty());
+    }
+
+    #[test]
+    fn tcp_bad_header() {
+        let mut header = Packet::new_stream_header(&bus());
+        header.header.set_version(0);
+
+        let imported = import(&[
+            tcp(ADAPTER, CLIENT, 0, SYN, &[]),
+            tcp(ADAPTER, CLIENT, 1, 0, header.as_bytes()),
+            tcp(ADAPTER, CLIENT, 31, 0, frame(1).as_bytes()),
+        ]);
+        assert_eq!(messages(&imported), [Err(DecodeError::Version)]);
+    }
+
+    #[test]
+    fn tcp_lost_segment() {
+        let header = Packet::new_stream_header(&bus());
+        let mut packets = vec![
+            tcp(ADAPTER, CLIENT, 0, SYN, &[]),
+            tcp(ADAPTER, CLIENT, 1, 0, header.as_bytes()),
+        ];
+
+        // the first frame and a half are lost, the rest are realigned
+        let mut sequence = 1 + HEADER_LEN as u32 + 21;
+        let mut stream = vec![];
+        for id in 1..=(MAX_AHEAD as u16 + 2) {
+            stream.extend(frame(id).as_bytes());
+        }
+        for chunk in stream[21..].chunks(FRAME_LEN) {
+            packets.push(tcp(ADAPTER, CLIENT, sequence, 0, chunk));
+            sequence += chunk.len() as u32;
+        }
+
+        let imported = import(&packets);
+        assert_eq!(
+            messages(&imported),
+            (3..=(MAX_AHEAD as u16 + 2))
+                .map(|id| Ok(Message::Frame(frame(id))))
+                .collect::<Vec<_>>()
+        );
+    }
+}
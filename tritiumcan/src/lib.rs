@@ -1,6 +1,17 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
+#[cfg(any(test, feature = "std"))]
+pub mod candump;
+#[cfg(any(test, feature = "std"))]
+pub mod capture;
 pub mod datagram;
+pub mod discovery;
+pub mod filter;
+#[cfg(any(test, feature = "std"))]
+pub mod import;
+pub mod link;
+pub mod queue;
+pub mod stream;
 
 use core::net::{IpAddr, Ipv4Addr};
 use core::time::Duration;
@@ -50,7 +61,7 @@ impl Flags {
 }
 
 /// Bus number
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct BusNumber(u8);
 
@@ -80,6 +91,60 @@ impl Default for BusNumber {
     }
 }
 
+/// CAN bus bitrate, announced in heartbeats.
+///
+/// The wire value is the bitrate in kbit/s. Values that do not match a
+/// supported rate are kept as [`Bitrate::Custom`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Bitrate {
+    Kbps50,
+    Kbps100,
+    Kbps125,
+    Kbps250,
+    Kbps500,
+    Kbps1000,
+    /// Non-standard bitrate in kbit/s.
+    Custom(u16),
+}
+
+impl Bitrate {
+    /// Bitrate in bit/s.
+    pub fn bps(&self) -> u32 {
+        u32::from(u16::from(*self)) * 1000
+    }
+}
+
+impl From<u16> for Bitrate {
+    /// Convert from the wire value in kbit/s.
+    fn from(value: u16) -> Self {
+        match value {
+            50 => Bitrate::Kbps50,
+            100 => Bitrate::Kbps100,
+            125 => Bitrate::Kbps125,
+            250 => Bitrate::Kbps250,
+            500 => Bitrate::Kbps500,
+            1000 => Bitrate::Kbps1000,
+            value => Bitrate::Custom(value),
+        }
+    }
+}
+
+impl From<Bitrate> for u16 {
+    /// Convert to the wire value in kbit/s.
+    fn from(value: Bitrate) -> Self {
+        match value {
+            Bitrate::Kbps50 => 50,
+            Bitrate::Kbps100 => 100,
+            Bitrate::Kbps125 => 125,
+            Bitrate::Kbps250 => 250,
+            Bitrate::Kbps500 => 500,
+            Bitrate::Kbps1000 => 1000,
+            Bitrate::Custom(value) => value,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,4 +157,15 @@ mod tests {
         assert!(BusNumber::try_from(16).is_err());
         assert!(BusNumber::try_from(255).is_err());
     }
+
+    #[test]
+    fn bitrate() {
+        for value in [50, 100, 125, 250, 500, 1000, 0, 83, u16::MAX] {
+            assert_eq!(u16::from(Bitrate::from(value)), value);
+        }
+
+        assert_eq!(Bitrate::from(500), Bitrate::Kbps500);
+        assert_eq!(Bitrate::from(83), Bitrate::Custom(83));
+        assert_eq!(Bitrate::Kbps125.bps(), 125_000);
+    }
 }
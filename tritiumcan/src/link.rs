@@ -0,0 +1,206 @@
+//! Heartbeat-loss detection.
+//!
+//! Time is passed in as a [`Duration`] since an arbitrary, monotonic epoch,
+//! the same as [`discovery`](crate::discovery).
+
+use crate::HEARTBEAT_INTERVAL;
+use core::time::Duration;
+
+/// Change in the state of a link to an adapter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Event {
+    /// The adapter missed too many heartbeats.
+    LinkLost { mac_addr: [u8; 6] },
+    /// A heartbeat was received from an adapter previously reported lost.
+    LinkRestored { mac_addr: [u8; 6] },
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+struct Link {
+    mac_addr: [u8; 6],
+    last_seen: Duration,
+    lost: bool,
+}
+
+/// Fixed capacity tracker of the last heartbeat from each adapter.
+///
+/// Unlike [`Discovery`](crate::discovery::Discovery), lost adapters are
+/// remembered so that their return can be reported.
+#[derive(Debug)]
+pub struct Monitor<const N: usize> {
+    links: [Option<Link>; N],
+    interval: Duration,
+    max_missed: u32,
+}
+
+impl<const N: usize> Monitor<N> {
+    /// Create an empty monitor, reporting a link lost after `max_missed`
+    /// heartbeat intervals of length `interval` without a heartbeat.
+    pub const fn new(interval: Duration, max_missed: u32) -> Self {
+        Self {
+            links: [None; N],
+            interval,
+            max_missed,
+        }
+    }
+
+    /// Interval adapters are expected to send heartbeats at.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Set the interval adapters are expected to send heartbeats at.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Number of missed heartbeats before a link is reported lost.
+    pub fn max_missed(&self) -> u32 {
+        self.max_missed
+    }
+
+    /// Set the number of missed heartbeats before a link is reported lost.
+    pub fn set_max_missed(&mut self, max_missed: u32) {
+        self.max_missed = max_missed;
+    }
+
+    /// Record a heartbeat from the adapter with `mac_addr`.
+    ///
+    /// When full, the longest lost adapter is forgotten to make room for a
+    /// new one, otherwise the heartbeat is ignored.
+    pub fn heartbeat(&mut self, mac_addr: &[u8; 6], now: Duration) {
+        if let Some(link) = self
+            .links
+            .iter_mut()
+            .flatten()
+            .find(|link| &link.mac_addr == mac_addr)
+        {
+            link.last_seen = now;
+            return;
+        }
+
+        let slot = match self.links.iter().position(Option::is_none) {
+            Some(n) => n,
+            None => {
+                let Some((n, _)) = self
+                    .links
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(n, link)| link.map(|link| (n, link)))
+                    .filter(|(_, link)| link.lost)
+                    .min_by_key(|(_, link)| link.last_seen)
+                else {
+                    return;
+                };
+                n
+            }
+        };
+
+        self.links[slot] = Some(Link {
+            mac_addr: *mac_addr,
+            last_seen: now,
+            lost: false,
+        });
+    }
+
+    /// Check for links that have been lost or restored since the last call.
+    ///
+    /// Returns one event per call, call until `None` is returned.
+    pub fn poll(&mut self, now: Duration) -> Option<Event> {
+        let timeout = self.interval * self.max_missed;
+
+        let link = self.links.iter_mut().flatten().find(|link| {
+            let alive = now.saturating_sub(link.last_seen) <= timeout;
+            alive == link.lost
+        })?;
+        link.lost = !link.lost;
+
+        let mac_addr = link.mac_addr;
+        Some(match link.lost {
+            true => Event::LinkLost { mac_addr },
+            false => Event::LinkRestored { mac_addr },
+        })
+    }
+
+    /// Whether the link to an adapter is lost, `None` if it is unknown.
+    pub fn is_lost(&self, mac_addr: &[u8; 6]) -> Option<bool> {
+        self.links
+            .iter()
+            .flatten()
+            .find(|link| &link.mac_addr == mac_addr)
+            .map(|link| link.lost)
+    }
+}
+
+impl<const N: usize> Default for Monitor<N> {
+    /// Report links lost after 3 missed heartbeats at the default
+    /// [`HEARTBEAT_INTERVAL`].
+    fn default() -> Self {
+        Self::new(HEARTBEAT_INTERVAL, 3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+
+    #[test]
+    fn lost_and_restored() {
+        let mut monitor = Monitor::<1>::new(HEARTBEAT_INTERVAL, 3);
+        let t = Duration::from_millis;
+
+        monitor.heartbeat(&MAC, t(0));
+        assert_eq!(monitor.poll(t(3000)), None);
+        assert_eq!(monitor.is_lost(&MAC), Some(false));
+
+        assert_eq!(
+            monitor.poll(t(3001)),
+            Some(Event::LinkLost { mac_addr: MAC })
+        );
+        assert_eq!(monitor.poll(t(5000)), None);
+        assert_eq!(monitor.is_lost(&MAC), Some(true));
+
+        monitor.heartbeat(&MAC, t(6000));
+        assert_eq!(
+            monitor.poll(t(6000)),
+            Some(Event::LinkRestored { mac_addr: MAC })
+        );
+        assert_eq!(monitor.poll(t(7000)), None);
+    }
+
+    #[test]
+    fn full() {
+        let mut monitor = Monitor::<1>::new(HEARTBEAT_INTERVAL, 1);
+        let other = [0x02, 0, 0, 0, 0, 2];
+        let t = Duration::from_millis;
+
+        monitor.heartbeat(&MAC, t(0));
+
+        // active links are kept
+        monitor.heartbeat(&other, t(0));
+        assert_eq!(monitor.is_lost(&other), None);
+
+        // lost links make room
+        assert!(monitor.poll(t(1001)).is_some());
+        monitor.heartbeat(&other, t(1001));
+        assert_eq!(monitor.is_lost(&other), Some(false));
+        assert_eq!(monitor.is_lost(&MAC), None);
+    }
+
+    #[test]
+    fn interval() {
+        let mut monitor = Monitor::<1>::new(Duration::from_secs(5), 2);
+        let t = Duration::from_millis;
+
+        monitor.heartbeat(&MAC, t(0));
+        assert_eq!(monitor.poll(t(10_000)), None);
+        assert_eq!(
+            monitor.poll(t(10_001)),
+            Some(Event::LinkLost { mac_addr: MAC })
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Transmit queue ordered by CAN arbitration priority.
+
+use crate::datagram::Frame;
+use embedded_can::Id;
+
+/// Arbitration priority of a frame, lower values win.
+///
+/// Mirrors bus arbitration: the 11 bit base identifier is compared first, a
+/// standard frame wins over an extended frame with the same base and a data
+/// frame wins over a remote frame with the same identifier.
+pub fn priority(frame: &impl embedded_can::Frame) -> u32 {
+    let remote = frame.is_remote_frame() as u32;
+
+    match frame.id() {
+        Id::Standard(id) => (id.as_raw() as u32) << 20 | remote,
+        Id::Extended(id) => {
+            let id = id.as_raw();
+            (id >> 18) << 20 | 1 << 19 | (id & 0x3FFFF) << 1 | remote
+        }
+    }
+}
+
+/// Bounded queue of frames, returning the highest priority frame first.
+///
+/// Frames with the same priority are returned in the order they were pushed.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Queue<const N: usize> {
+    frames: [Option<(u32, Frame)>; N],
+    seq: u32,
+}
+
+impl<const N: usize> Queue<N> {
+    pub const fn new() -> Self {
+        Self {
+            frames: [None; N],
+            seq: 0,
+        }
+    }
+
+    /// Add a frame, handing it back if the queue is full.
+    pub fn push(&mut self, frame: Frame) -> Result<(), Frame> {
+        let Some(slot) = self.frames.iter_mut().find(|slot| slot.is_none())
+        else {
+            return Err(frame);
+        };
+
+        *slot = Some((self.seq, frame));
+        self.seq = self.seq.wrapping_add(1);
+
+        Ok(())
+    }
+
+    /// Highest priority frame, without removing it.
+    pub fn peek(&self) -> Option<&Frame> {
+        self.next()
+            .and_then(|n| self.frames[n].as_ref().map(|(_, f)| f))
+    }
+
+    /// Remove and return the highest priority frame.
+    pub fn pop(&mut self) -> Option<Frame> {
+        let n = self.next()?;
+        self.frames[n].take().map(|(_, frame)| frame)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.iter().all(Option::is_none)
+    }
+
+    /// Drop all queued frames.
+    pub fn clear(&mut self) {
+        self.frames = [None; N];
+    }
+
+    fn next(&self) -> Option<usize> {
+        let seq = self.seq;
+
+        self.frames
+            .iter()
+            .enumerate()
+            .filter_map(|(n, slot)| slot.as_ref().map(|entry| (n, entry)))
+            // sequence numbers are compared relative to the next one so that
+            // wrapping keeps the push order
+            .min_by_key(|(_, (s, frame))| {
+                (priority(frame), s.wrapping_sub(seq))
+            })
+            .map(|(n, _)| n)
+    }
+}
+
+impl<const N: usize> Default for Queue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::{ExtendedId, StandardId};
+
+    fn frame(id: impl Into<Id>, data: &[u8]) -> Frame {
+        <Frame as embedded_can::Frame>::new(id, data).unwrap()
+    }
+
+    fn remote(id: impl Into<Id>) -> Frame {
+        <Frame as embedded_can::Frame>::new_remote(id, 0).unwrap()
+    }
+
+    #[test]
+    fn arbitration() {
+        let standard = StandardId::new(0x123).unwrap();
+        let extended = ExtendedId::new(0x123 << 18).unwrap();
+        let lower = ExtendedId::new(0x122 << 18 | 0x3FFFF).unwrap();
+
+        assert!(priority(&frame(standard, &[])) < priority(&remote(standard)));
+        assert!(priority(&remote(standard)) < priority(&frame(extended, &[])));
+        assert!(priority(&frame(extended, &[])) < priority(&remote(extended)));
+        assert!(priority(&frame(lower, &[])) < priority(&frame(standard, &[])));
+    }
+
+    #[test]
+    fn order() {
+        let mut queue = Queue::<4>::new();
+        let high = StandardId::new(0x010).unwrap();
+        let low = StandardId::new(0x700).unwrap();
+
+        queue.push(frame(low, &[1])).unwrap();
+        queue.push(frame(high, &[1])).unwrap();
+        queue.push(frame(low, &[2])).unwrap();
+        queue.push(frame(high, &[2])).unwrap();
+        assert_eq!(queue.push(frame(high, &[3])), Err(frame(high, &[3])));
+        assert_eq!(queue.len(), 4);
+
+        assert_eq!(queue.peek(), Some(&frame(high, &[1])));
+        assert_eq!(queue.pop(), Some(frame(high, &[1])));
+        assert_eq!(queue.pop(), Some(frame(high, &[2])));
+        assert_eq!(queue.pop(), Some(frame(low, &[1])));
+        assert_eq!(queue.pop(), Some(frame(low, &[2])));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+//! TCP stream framing.
+//!
+//! Both ends of a TCP connection start by sending a stream header (see
+//! [`Packet::new_stream_header`]) followed by any number of [`Frame`]s.
+
+use crate::datagram::{DecodeError, Frame, Header, Message, Packet, FRAME_LEN};
+use crate::{BusNumber, PROTOCOL_VERSION};
+use core::mem::size_of;
+use zerocopy::FromBytes;
+
+/// Stream header length.
+pub const HEADER_LEN: usize = size_of::<Packet>();
+
+/// Item decoded from a TCP stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Item {
+    /// Stream header sent by the remote end.
+    Header(Header),
+    /// Message received after the header.
+    Message(Message),
+}
+
+/// Incremental TCP stream decoder.
+///
+/// The decoder does not buffer any data itself, the caller keeps the received
+/// bytes and discards those consumed by [`Decoder::decode`].
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Decoder {
+    bus_number: Option<BusNumber>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self { bus_number: None }
+    }
+
+    /// Bus number announced in the stream header, if it has been received.
+    pub fn bus_number(&self) -> Option<BusNumber> {
+        self.bus_number
+    }
+
+    /// Decode the next item from the start of `buf`.
+    ///
+    /// Returns `None` if more bytes are needed, otherwise the number of bytes
+    /// consumed and the decoded item. Malformed frames are consumed so that
+    /// decoding can continue, but a bad stream header is not recoverable.
+    pub fn decode(
+        &mut self,
+        buf: &[u8],
+    ) -> Option<(usize, Result<Item, DecodeError>)> {
+        match self.bus_number {
+            None => {
+                let packet = Packet::read_from_prefix(buf)?;
+                Some((HEADER_LEN, self.decode_header(packet.header)))
+            }
+            Some(bus_number) => {
+                let frame = Frame::read_from_prefix(buf)?;
+                let message = Message::from_frame(frame, bus_number);
+                Some((FRAME_LEN, message.map(Item::Message)))
+            }
+        }
+    }
+
+    fn decode_header(&mut self, header: Header) -> Result<Item, DecodeError> {
+        if header.version() != PROTOCOL_VERSION {
+            return Err(DecodeError::Version);
+        }
+
+        let bus_number = BusNumber::try_from(header.bus_number())
+            .map_err(|_| DecodeError::BusNumber)?;
+        self.bus_number = Some(bus_number);
+
+        Ok(Item::Header(header))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_can::StandardId;
+    use zerocopy::AsBytes;
+
+    #[test]
+    fn header_then_frames() {
+        let bus_number = BusNumber::try_from(2).unwrap();
+        let header = Packet::new_stream_header(&bus_number);
+        let id = StandardId::new(0x10).unwrap();
+        let frame = <Frame as embedded_can::Frame>::new(id, &[1]).unwrap();
+
+        let mut buf = [0u8; HEADER_LEN + FRAME_LEN];
+        buf[..HEADER_LEN].copy_from_slice(header.as_bytes());
+        buf[HEADER_LEN..].copy_from_slice(AsBytes::as_bytes(&frame));
+
+        let mut decoder = Decoder::new();
+        assert!(decoder.decode(&buf[..HEADER_LEN - 1]).is_none());
+
+        let (len, item) = decoder.decode(&buf).unwrap();
+        assert_eq!(len, HEADER_LEN);
+        assert_eq!(item, Ok(Item::Header(header.header)));
+        assert_eq!(decoder.bus_number(), Some(bus_number));
+
+        let (len, item) = decoder.decode(&buf[HEADER_LEN..]).unwrap();
+        assert_eq!(len, FRAME_LEN);
+        assert_eq!(item, Ok(Item::Message(Message::Frame(frame))));
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut decoder = Decoder::new();
+        let (len, item) = decoder.decode(&[0; HEADER_LEN]).unwrap();
+        assert_eq!(len, HEADER_LEN);
+        assert_eq!(item, Err(DecodeError::Version));
+        assert_eq!(decoder.bus_number(), None);
+    }
+}
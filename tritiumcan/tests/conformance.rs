@@ -0,0 +1,186 @@
+//! Byte-exact wire format vectors, written out by hand from the adapter
+//! documentation rather than produced by the encoder under test.
+//!
+//! Every datagram starts with the 8 byte bus identifier, a zero byte, the 52
+//! bit protocol version and the 4 bit bus number, followed by the 8 byte
+//! client identifier. Frames follow as a big-endian identifier, a flags byte,
+//! a length byte and 8 data bytes in the order they appear on the bus.
+
+use embedded_can::{ExtendedId, Frame as CanFrame, Id, StandardId};
+use tritiumcan::{
+    datagram::{
+        Filter, FilterBitfield, Frame, Header, Heartbeat, Message, Packet,
+        FILTER_LEN,
+    },
+    stream::{Decoder, Item, HEADER_LEN},
+    Bitrate, BusNumber, PROTOCOL_VERSION,
+};
+use zerocopy::{AsBytes, FromBytes};
+
+/// Parse whitespace separated hex bytes.
+fn bytes(hex: &str) -> Vec<u8> {
+    hex.split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect()
+}
+
+fn bus(n: u8) -> BusNumber {
+    BusNumber::try_from(n).unwrap()
+}
+
+fn standard(id: u16) -> StandardId {
+    StandardId::new(id).unwrap()
+}
+
+fn extended(id: u32) -> ExtendedId {
+    ExtendedId::new(id).unwrap()
+}
+
+/// Bus identifier for bus 13 and a zero client identifier.
+const HEADER: &str = "00 54 72 69 74 69 75 6D  00 00 00 00 00 00 00 00";
+
+fn packet(frame: &str) -> Vec<u8> {
+    bytes(&format!("{HEADER} {frame}"))
+}
+
+/// Check a frame datagram against both the encoder and decoder.
+fn check_frame(wire: &[u8], frame: Frame) {
+    let packet = Packet::new_frame(&bus(13), &frame).unwrap();
+    assert_eq!(packet.as_bytes(), wire);
+
+    assert_eq!(Message::decode(wire), Ok(Message::Frame(frame)));
+}
+
+#[test]
+fn header() {
+    let wire = bytes(HEADER);
+    let header = Header::read_from(&wire[..]).unwrap();
+
+    assert_eq!(header.version(), PROTOCOL_VERSION);
+    assert_eq!(header.bus_number(), 13);
+    assert_eq!(header.client_identifier(), 0);
+    assert_eq!(Header::with_bus_number(&bus(13)).as_bytes(), wire);
+
+    let mut header = Header::with_bus_number(&bus(2));
+    header.set_client_identifier(0x11_2233_4455_6677);
+    assert_eq!(
+        header.as_bytes(),
+        bytes("00 54 72 69 74 69 75 62  00 11 22 33 44 55 66 77")
+    );
+}
+
+#[test]
+fn standard_frame() {
+    let wire = packet("00 00 01 23  00  03  01 02 03 00 00 00 00 00");
+    let frame = <Frame as CanFrame>::new(standard(0x123), &[1, 2, 3]).unwrap();
+
+    check_frame(&wire, frame);
+    assert_eq!(CanFrame::id(&frame), Id::Standard(standard(0x123)));
+    assert_eq!(CanFrame::data(&frame), [1, 2, 3]);
+}
+
+#[test]
+fn extended_frame() {
+    let wire = packet("1A BC DE F0  01  08  11 22 33 44 55 66 77 88");
+    let data = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+    let frame = <Frame as CanFrame>::new(extended(0x1ABC_DEF0), &data).unwrap();
+
+    check_frame(&wire, frame);
+    assert!(CanFrame::is_extended(&frame));
+    assert_eq!(CanFrame::data(&frame), data);
+}
+
+#[test]
+fn remote_frame() {
+    let wire = packet("00 00 07 FF  02  04  00 00 00 00 00 00 00 00");
+    let frame = <Frame as CanFrame>::new_remote(standard(0x7FF), 4).unwrap();
+
+    check_frame(&wire, frame);
+    assert!(CanFrame::is_remote_frame(&frame));
+    assert_eq!(CanFrame::dlc(&frame), 4);
+    assert_eq!(CanFrame::data(&frame), []);
+
+    let wire = packet("00 00 00 10  03  00  00 00 00 00 00 00 00 00");
+    let frame = <Frame as CanFrame>::new_remote(extended(0x10), 0).unwrap();
+
+    check_frame(&wire, frame);
+    assert!(CanFrame::is_extended(&frame));
+}
+
+#[test]
+fn heartbeat() {
+    // 500 kbit/s followed by the adapter MAC address
+    let wire = packet("00 00 00 00  80  08  01 F4 02 00 00 00 00 01");
+    let mac_addr = [0x02, 0, 0, 0, 0, 0x01];
+
+    let packet = Packet::new_heartbeat(&mac_addr, &bus(13), &Bitrate::Kbps500);
+    assert_eq!(packet.as_bytes(), wire);
+
+    assert_eq!(
+        Message::decode(&wire),
+        Ok(Message::Heartbeat(Heartbeat {
+            bus_number: bus(13),
+            data_rate: Bitrate::Kbps500,
+            mac_addr,
+        }))
+    );
+}
+
+#[test]
+fn settings() {
+    let wire = packet("00 00 00 00  40  08  00 00 00 00 00 00 00 00");
+
+    assert!(matches!(Message::decode(&wire), Ok(Message::Settings(_))));
+}
+
+#[test]
+fn stream() {
+    // a stream header is a whole datagram with an empty frame
+    let header = packet("00 00 00 00  00  00  00 00 00 00 00 00 00 00");
+    let frame = bytes("00 00 01 23  00  02  AA BB 00 00 00 00 00 00");
+    assert_eq!(header.len(), HEADER_LEN);
+
+    let packet = Packet::new_stream_header(&bus(13));
+    assert_eq!(packet.as_bytes(), header);
+
+    let mut decoder = Decoder::new();
+    let (len, item) = decoder.decode(&header).unwrap();
+    assert_eq!(len, HEADER_LEN);
+    assert_eq!(item, Ok(Item::Header(Header::with_bus_number(&bus(13)))));
+
+    let expected =
+        <Frame as CanFrame>::new(standard(0x123), &[0xAA, 0xBB]).unwrap();
+    assert_eq!(expected.as_bytes(), frame);
+
+    let (len, item) = decoder.decode(&frame).unwrap();
+    assert_eq!(len, frame.len());
+    assert_eq!(item, Ok(Item::Message(Message::Frame(expected))));
+}
+
+#[test]
+fn filter() {
+    // forward identifier and range, then the bus number, protocol version
+    // and client identifier. Unlike the other vectors this pins the existing
+    // encoding, no adapter capture of a filter packet is available yet.
+    let wire = bytes(
+        "00 00 01 00  00 00 00 FF
+         0D 54 72 69 74 69 75 60  00 00 00 00 00 00 02 A0",
+    );
+    assert_eq!(wire.len(), FILTER_LEN);
+
+    let mut filter = Filter::new();
+    filter.set_fwd_identifier(0x100);
+    filter.set_fwd_range(0xFF);
+    filter.set_version_number(PROTOCOL_VERSION);
+    filter.set_bus_number(13);
+    filter.set_client_identifier(42);
+    assert_eq!(filter.0.as_slice(), wire);
+
+    let filter =
+        FilterBitfield(<[u8; FILTER_LEN]>::try_from(&wire[..]).unwrap());
+    assert_eq!(filter.fwd_identifier(), 0x100);
+    assert_eq!(filter.fwd_range(), 0xFF);
+    assert_eq!(filter.version_number(), PROTOCOL_VERSION);
+    assert_eq!(filter.bus_number(), 13);
+    assert_eq!(filter.client_identifier(), 42);
+}
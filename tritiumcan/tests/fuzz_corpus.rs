@@ -0,0 +1,47 @@
+//! Replays the fuzzing corpus through the same checks as the fuzz targets, so
+//! that regressions are caught without a nightly toolchain.
+
+use std::{fs, path::Path};
+
+#[path = "../fuzz/checks.rs"]
+mod checks;
+
+fn replay(target: &str, check: fn(&[u8])) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/corpus")
+        .join(target);
+
+    let mut count = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        check(&fs::read(&path).unwrap());
+        count += 1;
+    }
+
+    assert!(count > 0, "empty corpus in {}", dir.display());
+}
+
+#[test]
+fn packet() {
+    replay("packet", checks::packet);
+}
+
+#[test]
+fn frame() {
+    replay("frame", checks::frame);
+}
+
+#[test]
+fn message() {
+    replay("message", checks::message);
+}
+
+#[test]
+fn filter() {
+    replay("filter", checks::filter);
+}
+
+#[test]
+fn stream() {
+    replay("stream", checks::stream);
+}